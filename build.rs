@@ -1,9 +1,13 @@
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     tonic_build::configure()
-        .build_server(false)
+        .build_server(true)
         .out_dir("src/pursuit/api/mortalkin")
         .compile(
-            &["mortalkin/user.proto", "mortalkin/game.proto"],
+            &[
+                "mortalkin/user.proto",
+                "mortalkin/game.proto",
+                "mortalkin/map.proto",
+            ],
             &["../shared/proto/api"],
         )?;
 
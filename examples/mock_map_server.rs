@@ -0,0 +1,59 @@
+// Serves the maps in `assets/map` over the Map service, so map downloads and
+// the cache can be tried without the game server:
+// `cargo run --example mock_map_server [address]`, then run the client with
+// `--map-server http://[::1]:5005`.
+use std::fs;
+
+use bev::pursuit::api::mortalkin::map_server::{Map, MapServer};
+use bev::pursuit::api::mortalkin::{GetMapPayload, GetMapResponse};
+use bev::system::map;
+
+use sha2::{Digest, Sha256};
+use tonic::{transport::Server, Request, Response, Status};
+
+// Next to the game server's port, so both can run at once.
+const DEFAULT_ADDR: &str = "[::1]:5005";
+// Bump it to make every client download the maps again.
+const MAP_VERSION: u32 = 1;
+
+struct MockMaps;
+
+#[tonic::async_trait]
+impl Map for MockMaps {
+    async fn get_map(
+        &self,
+        request: Request<GetMapPayload>,
+    ) -> Result<Response<GetMapResponse>, Status> {
+        let payload = request.into_inner();
+        let content = fs::read(map::zone_path(payload.zone_id))
+            .map_err(|_| Status::not_found(format!("no map for zone {}", payload.zone_id)))?;
+
+        let mut response = GetMapResponse {
+            zone_id: payload.zone_id,
+            version: MAP_VERSION,
+            hash: format!("{:x}", Sha256::digest(&content)),
+            content: Vec::new(),
+        };
+        if payload.version != MAP_VERSION {
+            response.content = content;
+        }
+
+        Ok(Response::new(response))
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let addr = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| DEFAULT_ADDR.to_string())
+        .parse()?;
+    println!("Serving maps on {}", addr);
+
+    Server::builder()
+        .add_service(MapServer::new(MockMaps))
+        .serve(addr)
+        .await?;
+
+    Ok(())
+}
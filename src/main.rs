@@ -3,7 +3,9 @@ use std::sync::Mutex;
 use std::thread;
 use std::time::Duration;
 
+use bev::pursuit::api::mortalkin;
 use bev::pursuit::api::mortalkin::game_client::GameClient;
+use bev::pursuit::api::mortalkin::map_client::MapClient;
 use bev::pursuit::api::mortalkin::user_client::UserClient;
 use bev::system;
 
@@ -19,6 +21,11 @@ use tokio::time;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // The editor works on local map files, it doesn't talk to the server.
+    if run_editor() {
+        return Ok(());
+    }
+
     let mut grpc_client_login = create_grpc_client().await;
     let (request_sender, request_receiver) = mpsc::channel();
     let (response_sender, response_receiver) = mpsc::channel();
@@ -39,16 +46,37 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         create_char_response_sender.send(resp).unwrap();
     });
 
+    let mut grpc_client_map = create_grpc_client_map().await;
+    let (map_request_sender, map_request_receiver) = mpsc::channel();
+    let (map_response_sender, map_response_receiver) = mpsc::channel();
+    thread::spawn(move || loop {
+        let payload = map_request_receiver.recv().unwrap();
+        let response = grpc_client_map.get_map(payload);
+        let resp = block_on(response);
+        map_response_sender.send(resp).unwrap();
+    });
+
+    let mut grpc_client_respawn = create_grpc_client_game().await;
+    let (respawn_request_sender, respawn_request_receiver) = mpsc::channel();
+    let (respawn_response_sender, respawn_response_receiver) = mpsc::channel();
+    thread::spawn(move || loop {
+        let payload = respawn_request_receiver.recv().unwrap();
+        let response = grpc_client_respawn.respawn(payload);
+        let resp = block_on(response);
+        respawn_response_sender.send(resp).unwrap();
+    });
+
     let mut grpc_client_play = create_grpc_client_game().await;
     let (play_request_sender, mut play_request_receiver) = futures::channel::mpsc::unbounded();
     let (play_response_sender, play_response_receiver) = futures::channel::mpsc::unbounded();
 
     let outbound = async_stream::stream! {
-        let mut interval = time::interval(Duration::from_secs(1));
+        let mut interval = time::interval(Duration::from_millis(50));
 
         while let _ = interval.tick().await {
-            let next_payload = play_request_receiver.try_next();
-            if let Ok(Some(payload)) = next_payload {
+            // Movement sends a payload per step, so drain everything queued
+            // since the last tick instead of one payload at a time.
+            while let Ok(Some(payload)) = play_request_receiver.try_next() {
                 yield payload;
             }
         }
@@ -70,10 +98,48 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .init_resource::<system::ButtonMaterials>()
         .init_resource::<system::TileSpriteHandles>()
         .init_resource::<system::GameMap>()
+        .init_resource::<system::field::AnimatedTiles>()
+        .init_resource::<system::movement::MovementMode>()
+        .init_resource::<system::animation::CharacterAnimations>()
+        .init_resource::<system::camera::CameraRig>()
+        .init_resource::<system::picking::Hover>()
+        .init_resource::<system::pathfinding::TrailMaterial>()
+        .init_resource::<system::zone::ZoneTransition>()
+        .init_resource::<system::minimap::Minimap>()
+        .init_resource::<system::minimap::WorldMap>()
+        .init_resource::<system::minimap::Waypoints>()
+        .init_resource::<system::fog::Fog>()
+        .init_resource::<system::lighting::WorldClock>()
+        .init_resource::<system::lighting::Lighting>()
+        .init_resource::<system::weather::Weather>()
+        .init_resource::<system::weather::Puddles>()
+        .init_resource::<system::nameplate::Nameplates>()
+        .init_resource::<system::chat::Chat>()
+        .init_resource::<system::emote::Emotes>()
+        .init_resource::<system::dialogue::Dialogue>()
+        .init_resource::<system::interaction::Interactions>()
+        .init_resource::<system::mob::MobSprites>()
+        .init_resource::<system::mob::Mobs>()
+        .init_resource::<system::target::Target>()
+        .init_resource::<system::combat::Combat>()
+        .init_resource::<system::death::Respawn>()
+        .add_event::<system::animation::PlayClip>()
+        .add_event::<system::camera::ShakeCamera>()
+        .add_event::<mortalkin::Character>()
+        .add_event::<system::lighting::ServerTime>()
+        .add_event::<mortalkin::Weather>()
+        .add_event::<mortalkin::ChatMessage>()
+        .add_event::<system::emote::UseEmote>()
+        .add_event::<mortalkin::Emote>()
+        .add_event::<mortalkin::InteractionResult>()
+        .add_event::<mortalkin::Mob>()
+        .add_event::<mortalkin::Damage>()
+        .add_event::<mortalkin::Death>()
         .add_plugin(FrameTimeDiagnosticsPlugin::default())
         .add_startup_system(setup_fps.system())
         .add_startup_system(system::setup_camera.system())
         .add_startup_system(system::setup_tile.system())
+        .add_startup_system(system::animation::setup.system())
         .add_system(fps_update_system.system())
         .insert_resource(system::RequestSender {
             tx: Mutex::new(play_request_sender),
@@ -81,6 +147,19 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .insert_resource(system::ResponseReceiver {
             rx: Mutex::new(play_response_receiver),
         })
+        .insert_resource(system::map_cache::CacheIndex::load())
+        .insert_resource(system::map_cache::MapRequestSender {
+            tx: Mutex::new(map_request_sender),
+        })
+        .insert_resource(system::map_cache::MapResponseReceiver {
+            rx: Mutex::new(map_response_receiver),
+        })
+        .insert_resource(system::death::RespawnRequestSender {
+            tx: Mutex::new(respawn_request_sender),
+        })
+        .insert_resource(system::death::RespawnResponseReceiver {
+            rx: Mutex::new(respawn_response_receiver),
+        })
         .insert_resource(system::login::LoginAction::new())
         .insert_resource(system::login::LoginRequestSender {
             tx: Mutex::new(request_sender),
@@ -136,18 +215,153 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             SystemSet::on_exit(system::AppState::CharCreationMenu)
                 .with_system(system::char_creation::cleanup.system()),
         )
+        .add_system_set(
+            SystemSet::on_enter(system::AppState::Field)
+                .with_system(system::camera::spawn.system())
+                .with_system(system::picking::setup.system())
+                .with_system(system::zone::setup.system())
+                .with_system(system::minimap::setup.system())
+                .with_system(system::lighting::setup.system())
+                .with_system(system::weather::setup.system())
+                .with_system(system::chat::setup.system())
+                .with_system(system::emote::setup.system())
+                .with_system(system::dialogue::setup.system())
+                .with_system(system::interaction::setup.system())
+                .with_system(system::target::setup.system())
+                .with_system(system::combat::setup.system())
+                .with_system(system::death::setup.system()),
+        )
         .add_system_set(
             SystemSet::on_update(system::AppState::Field)
                 .with_system(system::field::load.system())
                 .with_system(system::field::build.system())
-                .with_system(system::character_movement.system())
-                .with_system(system::incoming_notif.system()),
+                .with_system(system::field::animate_tiles.system())
+                .with_system(system::collision::sync_blockers.system())
+                .with_system(system::movement::read_input.system().label("move_input"))
+                .with_system(system::pathfinding::click_to_move.system())
+                .with_system(
+                    system::pathfinding::follow_path
+                        .system()
+                        .after("move_input")
+                        .before("move_step"),
+                )
+                .with_system(system::movement::toggle_mode.system().before("move_step"))
+                .with_system(system::movement::step.system().label("move_step"))
+                .with_system(system::movement::advance.system().before("move_step"))
+                .with_system(system::camera::zoom.system())
+                .with_system(system::camera::shake.system())
+                .with_system(system::camera::follow.system())
+                .with_system(system::place_sprites.system())
+                .with_system(system::props::fade_props.system())
+                .with_system(system::nameplate::spawn.system())
+                .with_system(system::nameplate::toggle_health.system())
+                .with_system(system::nameplate::update.system())
+                .with_system(system::chat::receive.system())
+                .with_system(system::chat::input.system())
+                .with_system(system::chat::refresh.system())
+                .with_system(system::chat::spawn_bubbles.system())
+                .with_system(system::chat::update_bubbles.system())
+                .with_system(system::emote::toggle_menu.system())
+                .with_system(system::emote::menu_input.system())
+                .with_system(system::emote::use_emotes.system())
+                .with_system(system::emote::play.system())
+                .with_system(system::interaction::detect.system())
+                .with_system(
+                    system::interaction::interact
+                        .system()
+                        .before("dialogue_input"),
+                )
+                .with_system(system::interaction::resolve.system())
+                .with_system(system::dialogue::input.system().label("dialogue_input"))
+                .with_system(system::dialogue::typewrite.system())
+                .with_system(system::picking::hover.system())
+                .with_system(system::mob::update.system())
+                .with_system(system::mob::animate.system())
+                .with_system(system::target::select.system())
+                .with_system(system::target::update_frame.system())
+                .with_system(system::combat::attack.system())
+                .with_system(system::combat::apply.system())
+                .with_system(system::combat::fade.system())
+                .with_system(system::combat::update_player_frame.system())
+                .with_system(system::death::countdown.system())
+                .with_system(system::death::respawn.system())
+                .with_system(system::death::revive.system())
+                .with_system(system::animation::build_atlas.system())
+                .with_system(system::animation::play_events.system())
+                .with_system(
+                    system::animation::drive_from_movement
+                        .system()
+                        .after("move_step"),
+                )
+                .with_system(system::animation::animate.system())
+                .with_system(system::minimap::read_map.system())
+                .with_system(system::minimap::refresh_minimap.system())
+                .with_system(system::minimap::toggle_world_map.system())
+                .with_system(system::minimap::world_map_input.system())
+                .with_system(system::fog::reveal.system())
+                .with_system(system::fog::save_explored.system())
+                .with_system(system::lighting::spawn_lights.system())
+                .with_system(system::lighting::sync_clock.system())
+                .with_system(system::lighting::update.system())
+                .with_system(system::weather::receive.system())
+                .with_system(system::weather::update.system())
+                .with_system(system::weather::puddles.system())
+                .with_system(system::particles::emit.system())
+                .with_system(system::particles::simulate.system())
+                .with_system(system::zone::enter_portals.system())
+                .with_system(system::zone::transition.system())
+                .with_system(system::incoming_notif.system())
+                .with_system(system::update_players.system()),
         )
         .run();
 
     Ok(())
 }
 
+#[cfg(feature = "editor")]
+fn run_editor() -> bool {
+    let zone_id = match system::editor::zone_from_args() {
+        Some(zone_id) => zone_id,
+        None => return false,
+    };
+
+    App::build()
+        .add_plugins(DefaultPlugins)
+        .add_plugins(TilemapDefaultPlugins)
+        .add_state(system::AppState::Editor)
+        .init_resource::<system::TileSpriteHandles>()
+        .init_resource::<system::GameMap>()
+        .init_resource::<system::camera::CameraRig>()
+        .init_resource::<system::minimap::WorldMap>()
+        .init_resource::<system::chat::Chat>()
+        .insert_resource(system::editor::Editor::new(zone_id))
+        .add_startup_system(system::setup_camera.system())
+        .add_startup_system(system::setup_tile.system())
+        .add_system_set(
+            SystemSet::on_enter(system::AppState::Editor)
+                .with_system(system::camera::spawn.system())
+                .with_system(system::editor::setup.system()),
+        )
+        .add_system_set(
+            SystemSet::on_update(system::AppState::Editor)
+                .with_system(system::editor::load.system())
+                .with_system(system::editor::pick_from_palette.system())
+                .with_system(system::editor::keyboard.system())
+                .with_system(system::editor::paint.system())
+                .with_system(system::editor::redraw.system())
+                .with_system(system::camera::zoom.system())
+                .with_system(system::camera::follow.system()),
+        )
+        .run();
+
+    true
+}
+
+#[cfg(not(feature = "editor"))]
+fn run_editor() -> bool {
+    false
+}
+
 async fn create_grpc_client() -> UserClient<tonic::transport::Channel> {
     let channel = tonic::transport::Channel::from_static("http://[::1]:5004")
         .connect()
@@ -166,6 +380,16 @@ async fn create_grpc_client_game() -> GameClient<tonic::transport::Channel> {
     GameClient::new(channel)
 }
 
+async fn create_grpc_client_map() -> MapClient<tonic::transport::Channel> {
+    let channel = tonic::transport::Channel::from_shared(system::map_cache::server_from_args())
+        .expect("Invalid map server address")
+        .connect()
+        .await
+        .expect("Can't create a channel");
+
+    MapClient::new(channel)
+}
+
 // A unit struct to help identify the FPS UI component, since there may be many Text components
 struct FpsText;
 
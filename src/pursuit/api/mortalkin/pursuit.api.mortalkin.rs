@@ -6,11 +6,83 @@ pub struct PlayGamePayload {
     pub character_id: u32,
     #[prost(message, optional, tag = "3")]
     pub position: ::core::option::Option<Position>,
+    #[prost(message, optional, tag = "4")]
+    pub enter_portal: ::core::option::Option<EnterPortal>,
+    #[prost(message, optional, tag = "5")]
+    pub chat: ::core::option::Option<ChatMessage>,
+    #[prost(message, optional, tag = "6")]
+    pub emote: ::core::option::Option<Emote>,
+    #[prost(message, optional, tag = "7")]
+    pub interact: ::core::option::Option<Interact>,
+    #[prost(message, optional, tag = "8")]
+    pub attack: ::core::option::Option<Attack>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct EnterPortal {
+    #[prost(message, optional, tag = "1")]
+    pub portal: ::core::option::Option<Position>,
+    #[prost(message, optional, tag = "2")]
+    pub destination: ::core::option::Option<Position>,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct GameNotif {
     #[prost(message, repeated, tag = "1")]
     pub characters: ::prost::alloc::vec::Vec<Character>,
+    /// Server clock when the notification was sent, in milliseconds since the
+    /// unix epoch.
+    #[prost(uint64, tag = "2")]
+    pub server_time: u64,
+    /// Weather of the zones where it changed, and of the zone the character
+    /// just entered.
+    #[prost(message, repeated, tag = "3")]
+    pub weather: ::prost::alloc::vec::Vec<Weather>,
+    /// Every message is also sent back to its sender.
+    #[prost(message, repeated, tag = "4")]
+    pub chat: ::prost::alloc::vec::Vec<ChatMessage>,
+    /// Also sent back to the character who did it.
+    #[prost(message, repeated, tag = "5")]
+    pub emotes: ::prost::alloc::vec::Vec<Emote>,
+    /// Sent to everyone in the zone, so a door opened by someone opens for
+    /// everybody.
+    #[prost(message, repeated, tag = "6")]
+    pub interactions: ::prost::alloc::vec::Vec<InteractionResult>,
+    /// Mobs that appeared, moved or whose hit points changed. One that's now in
+    /// another zone than the character has left it.
+    #[prost(message, repeated, tag = "7")]
+    pub mobs: ::prost::alloc::vec::Vec<Mob>,
+    /// Hits and deaths of everyone in the character's zone.
+    #[prost(message, repeated, tag = "8")]
+    pub damage: ::prost::alloc::vec::Vec<Damage>,
+    #[prost(message, repeated, tag = "9")]
+    pub deaths: ::prost::alloc::vec::Vec<Death>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ChatMessage {
+    #[prost(enumeration = "ChatChannel", tag = "1")]
+    pub channel: i32,
+    /// Filled in by the server, 0 for system messages.
+    #[prost(uint32, tag = "2")]
+    pub from_id: u32,
+    #[prost(string, tag = "3")]
+    pub from_name: ::prost::alloc::string::String,
+    /// Name of the character a whisper is for.
+    #[prost(string, tag = "4")]
+    pub to_name: ::prost::alloc::string::String,
+    #[prost(string, tag = "5")]
+    pub text: ::prost::alloc::string::String,
+    /// Server clock when it was sent, in milliseconds since the unix epoch.
+    #[prost(uint64, tag = "6")]
+    pub sent_at: u64,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Weather {
+    #[prost(uint32, tag = "1")]
+    pub zone_id: u32,
+    #[prost(enumeration = "WeatherKind", tag = "2")]
+    pub kind: i32,
+    /// From 0 to 1.
+    #[prost(float, tag = "3")]
+    pub intensity: f32,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Character {
@@ -20,6 +92,14 @@ pub struct Character {
     pub name: ::prost::alloc::string::String,
     #[prost(message, optional, tag = "3")]
     pub position: ::core::option::Option<Position>,
+    /// 0 when the character isn't in a party.
+    #[prost(uint32, tag = "4")]
+    pub party_id: u32,
+    /// Both 0 when the server doesn't tell.
+    #[prost(uint32, tag = "5")]
+    pub hp: u32,
+    #[prost(uint32, tag = "6")]
+    pub max_hp: u32,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Position {
@@ -27,6 +107,160 @@ pub struct Position {
     pub x: i32,
     #[prost(int32, tag = "2")]
     pub y: i32,
+    #[prost(uint32, tag = "3")]
+    pub zone_id: u32,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Emote {
+    /// Filled in by the server.
+    #[prost(uint32, tag = "1")]
+    pub character_id: u32,
+    #[prost(enumeration = "EmoteKind", tag = "2")]
+    pub kind: i32,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Interact {
+    #[prost(enumeration = "InteractionKind", tag = "1")]
+    pub kind: i32,
+    /// Tile of what the character wants to use.
+    #[prost(message, optional, tag = "2")]
+    pub target: ::core::option::Option<Position>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct InteractionResult {
+    #[prost(uint32, tag = "1")]
+    pub character_id: u32,
+    #[prost(enumeration = "InteractionKind", tag = "2")]
+    pub kind: i32,
+    #[prost(message, optional, tag = "3")]
+    pub target: ::core::option::Option<Position>,
+    #[prost(bool, tag = "4")]
+    pub allowed: bool,
+    /// What happened, or why it wasn't allowed. Only for the character who
+    /// tried.
+    #[prost(string, tag = "5")]
+    pub message: ::prost::alloc::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Mob {
+    #[prost(uint32, tag = "1")]
+    pub id: u32,
+    #[prost(enumeration = "MobKind", tag = "2")]
+    pub kind: i32,
+    #[prost(message, optional, tag = "3")]
+    pub position: ::core::option::Option<Position>,
+    #[prost(uint32, tag = "4")]
+    pub hp: u32,
+    #[prost(uint32, tag = "5")]
+    pub max_hp: u32,
+}
+/// A character or a mob, by id.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Fighter {
+    #[prost(enumeration = "FighterKind", tag = "1")]
+    pub kind: i32,
+    #[prost(uint32, tag = "2")]
+    pub id: u32,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Attack {
+    /// The server checks it's close enough and the attacker isn't cooling down.
+    #[prost(message, optional, tag = "1")]
+    pub target: ::core::option::Option<Fighter>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Damage {
+    #[prost(message, optional, tag = "1")]
+    pub source: ::core::option::Option<Fighter>,
+    #[prost(message, optional, tag = "2")]
+    pub target: ::core::option::Option<Fighter>,
+    #[prost(uint32, tag = "3")]
+    pub amount: u32,
+    /// Hit points of the target after the hit.
+    #[prost(uint32, tag = "4")]
+    pub hp: u32,
+    #[prost(uint32, tag = "5")]
+    pub max_hp: u32,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Death {
+    #[prost(message, optional, tag = "1")]
+    pub fighter: ::core::option::Option<Fighter>,
+    #[prost(message, optional, tag = "2")]
+    pub killer: ::core::option::Option<Fighter>,
+    /// Seconds a character waits before it can respawn.
+    #[prost(uint32, tag = "3")]
+    pub respawn_seconds: u32,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RespawnPayload {
+    #[prost(string, tag = "1")]
+    pub token: ::prost::alloc::string::String,
+    #[prost(uint32, tag = "2")]
+    pub character_id: u32,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RespawnResponse {
+    /// Where the server placed the character, and its hit points there.
+    #[prost(message, optional, tag = "1")]
+    pub position: ::core::option::Option<Position>,
+    #[prost(uint32, tag = "2")]
+    pub hp: u32,
+    #[prost(uint32, tag = "3")]
+    pub max_hp: u32,
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum ChatChannel {
+    /// Players in the same zone close to the sender.
+    Say = 0,
+    /// Every player in the same zone.
+    Zone = 1,
+    Whisper = 2,
+    /// From the server itself, never sent by clients.
+    System = 3,
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum WeatherKind {
+    Clear = 0,
+    Rain = 1,
+    Snow = 2,
+    Fog = 3,
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum EmoteKind {
+    Wave = 0,
+    Sit = 1,
+    Dance = 2,
+    Cheer = 3,
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum InteractionKind {
+    Talk = 0,
+    Open = 1,
+    PickUp = 2,
+    ReadSign = 3,
+    UsePortal = 4,
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum MobKind {
+    SlimeBlue = 0,
+    SlimeGreen = 1,
+    SlimeOrange = 2,
+    Fox = 3,
+    Kobold = 4,
+    Worm = 5,
+    BossBee = 6,
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum FighterKind {
+    Character = 0,
+    Mob = 1,
 }
 #[doc = r" Generated client implementations."]
 pub mod game_client {
@@ -78,6 +312,22 @@ pub mod game_client {
                 .streaming(request.into_streaming_request(), path, codec)
                 .await
         }
+        #[doc = " Brings a dead character back at its spawn point, once it has waited long"]
+        #[doc = " enough."]
+        pub async fn respawn(
+            &mut self,
+            request: impl tonic::IntoRequest<super::RespawnPayload>,
+        ) -> Result<tonic::Response<super::RespawnResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/pursuit.api.mortalkin.Game/Respawn");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
     }
     impl<T: Clone> Clone for GameClient<T> {
         fn clone(&self) -> Self {
@@ -92,6 +342,156 @@ pub mod game_client {
         }
     }
 }
+#[doc = r" Generated server implementations."]
+pub mod game_server {
+    #![allow(unused_variables, dead_code, missing_docs)]
+    use tonic::codegen::*;
+    #[doc = "Generated trait containing gRPC methods that should be implemented for use with GameServer."]
+    #[async_trait]
+    pub trait Game: Send + Sync + 'static {
+        #[doc = "Server streaming response type for the Play method."]
+        type PlayStream: futures_core::Stream<Item = Result<super::GameNotif, tonic::Status>>
+            + Send
+            + Sync
+            + 'static;
+        async fn play(
+            &self,
+            request: tonic::Request<tonic::Streaming<super::PlayGamePayload>>,
+        ) -> Result<tonic::Response<Self::PlayStream>, tonic::Status>;
+        #[doc = " Brings a dead character back at its spawn point, once it has waited long"]
+        #[doc = " enough."]
+        async fn respawn(
+            &self,
+            request: tonic::Request<super::RespawnPayload>,
+        ) -> Result<tonic::Response<super::RespawnResponse>, tonic::Status>;
+    }
+    #[derive(Debug)]
+    pub struct GameServer<T: Game> {
+        inner: _Inner<T>,
+    }
+    struct _Inner<T>(Arc<T>, Option<tonic::Interceptor>);
+    impl<T: Game> GameServer<T> {
+        pub fn new(inner: T) -> Self {
+            let inner = Arc::new(inner);
+            let inner = _Inner(inner, None);
+            Self { inner }
+        }
+        pub fn with_interceptor(inner: T, interceptor: impl Into<tonic::Interceptor>) -> Self {
+            let inner = Arc::new(inner);
+            let inner = _Inner(inner, Some(interceptor.into()));
+            Self { inner }
+        }
+    }
+    impl<T, B> Service<http::Request<B>> for GameServer<T>
+    where
+        T: Game,
+        B: HttpBody + Send + Sync + 'static,
+        B::Error: Into<StdError> + Send + 'static,
+    {
+        type Response = http::Response<tonic::body::BoxBody>;
+        type Error = Never;
+        type Future = BoxFuture<Self::Response, Self::Error>;
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+        fn call(&mut self, req: http::Request<B>) -> Self::Future {
+            let inner = self.inner.clone();
+            match req.uri().path() {
+                "/pursuit.api.mortalkin.Game/Play" => {
+                    #[allow(non_camel_case_types)]
+                    struct PlaySvc<T: Game>(pub Arc<T>);
+                    impl<T: Game> tonic::server::StreamingService<super::PlayGamePayload> for PlaySvc<T> {
+                        type Response = super::GameNotif;
+                        type ResponseStream = T::PlayStream;
+                        type Future =
+                            BoxFuture<tonic::Response<Self::ResponseStream>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<tonic::Streaming<super::PlayGamePayload>>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).play(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1;
+                        let inner = inner.0;
+                        let method = PlaySvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pursuit.api.mortalkin.Game/Respawn" => {
+                    #[allow(non_camel_case_types)]
+                    struct RespawnSvc<T: Game>(pub Arc<T>);
+                    impl<T: Game> tonic::server::UnaryService<super::RespawnPayload> for RespawnSvc<T> {
+                        type Response = super::RespawnResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::RespawnPayload>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).respawn(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = RespawnSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                _ => Box::pin(async move {
+                    Ok(http::Response::builder()
+                        .status(200)
+                        .header("grpc-status", "12")
+                        .header("content-type", "application/grpc")
+                        .body(tonic::body::BoxBody::empty())
+                        .unwrap())
+                }),
+            }
+        }
+    }
+    impl<T: Game> Clone for GameServer<T> {
+        fn clone(&self) -> Self {
+            let inner = self.inner.clone();
+            Self { inner }
+        }
+    }
+    impl<T: Game> Clone for _Inner<T> {
+        fn clone(&self) -> Self {
+            Self(self.0.clone(), self.1.clone())
+        }
+    }
+    impl<T: std::fmt::Debug> std::fmt::Debug for _Inner<T> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{:?}", self.0)
+        }
+    }
+    impl<T: Game> tonic::transport::NamedService for GameServer<T> {
+        const NAME: &'static str = "pursuit.api.mortalkin.Game";
+    }
+}
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct LoginPayload {
     #[prost(string, tag = "1")]
@@ -189,3 +589,333 @@ pub mod user_client {
         }
     }
 }
+#[doc = r" Generated server implementations."]
+pub mod user_server {
+    #![allow(unused_variables, dead_code, missing_docs)]
+    use tonic::codegen::*;
+    #[doc = "Generated trait containing gRPC methods that should be implemented for use with UserServer."]
+    #[async_trait]
+    pub trait User: Send + Sync + 'static {
+        async fn login(
+            &self,
+            request: tonic::Request<super::LoginPayload>,
+        ) -> Result<tonic::Response<super::LoginResponse>, tonic::Status>;
+        async fn create_character(
+            &self,
+            request: tonic::Request<super::CreateCharacterPayload>,
+        ) -> Result<tonic::Response<super::Character>, tonic::Status>;
+    }
+    #[derive(Debug)]
+    pub struct UserServer<T: User> {
+        inner: _Inner<T>,
+    }
+    struct _Inner<T>(Arc<T>, Option<tonic::Interceptor>);
+    impl<T: User> UserServer<T> {
+        pub fn new(inner: T) -> Self {
+            let inner = Arc::new(inner);
+            let inner = _Inner(inner, None);
+            Self { inner }
+        }
+        pub fn with_interceptor(inner: T, interceptor: impl Into<tonic::Interceptor>) -> Self {
+            let inner = Arc::new(inner);
+            let inner = _Inner(inner, Some(interceptor.into()));
+            Self { inner }
+        }
+    }
+    impl<T, B> Service<http::Request<B>> for UserServer<T>
+    where
+        T: User,
+        B: HttpBody + Send + Sync + 'static,
+        B::Error: Into<StdError> + Send + 'static,
+    {
+        type Response = http::Response<tonic::body::BoxBody>;
+        type Error = Never;
+        type Future = BoxFuture<Self::Response, Self::Error>;
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+        fn call(&mut self, req: http::Request<B>) -> Self::Future {
+            let inner = self.inner.clone();
+            match req.uri().path() {
+                "/pursuit.api.mortalkin.User/Login" => {
+                    #[allow(non_camel_case_types)]
+                    struct LoginSvc<T: User>(pub Arc<T>);
+                    impl<T: User> tonic::server::UnaryService<super::LoginPayload> for LoginSvc<T> {
+                        type Response = super::LoginResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::LoginPayload>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).login(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = LoginSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pursuit.api.mortalkin.User/CreateCharacter" => {
+                    #[allow(non_camel_case_types)]
+                    struct CreateCharacterSvc<T: User>(pub Arc<T>);
+                    impl<T: User> tonic::server::UnaryService<super::CreateCharacterPayload> for CreateCharacterSvc<T> {
+                        type Response = super::Character;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::CreateCharacterPayload>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).create_character(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = CreateCharacterSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                _ => Box::pin(async move {
+                    Ok(http::Response::builder()
+                        .status(200)
+                        .header("grpc-status", "12")
+                        .header("content-type", "application/grpc")
+                        .body(tonic::body::BoxBody::empty())
+                        .unwrap())
+                }),
+            }
+        }
+    }
+    impl<T: User> Clone for UserServer<T> {
+        fn clone(&self) -> Self {
+            let inner = self.inner.clone();
+            Self { inner }
+        }
+    }
+    impl<T: User> Clone for _Inner<T> {
+        fn clone(&self) -> Self {
+            Self(self.0.clone(), self.1.clone())
+        }
+    }
+    impl<T: std::fmt::Debug> std::fmt::Debug for _Inner<T> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{:?}", self.0)
+        }
+    }
+    impl<T: User> tonic::transport::NamedService for UserServer<T> {
+        const NAME: &'static str = "pursuit.api.mortalkin.User";
+    }
+}
+/// `version` is the one the client has cached, 0 for none.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetMapPayload {
+    #[prost(string, tag = "1")]
+    pub token: ::prost::alloc::string::String,
+    #[prost(uint32, tag = "2")]
+    pub zone_id: u32,
+    #[prost(uint32, tag = "3")]
+    pub version: u32,
+}
+/// `content` is left empty when the cached version is still current.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetMapResponse {
+    #[prost(uint32, tag = "1")]
+    pub zone_id: u32,
+    #[prost(uint32, tag = "2")]
+    pub version: u32,
+    #[prost(string, tag = "3")]
+    pub hash: ::prost::alloc::string::String,
+    #[prost(bytes = "vec", tag = "4")]
+    pub content: ::prost::alloc::vec::Vec<u8>,
+}
+#[doc = r" Generated client implementations."]
+pub mod map_client {
+    #![allow(unused_variables, dead_code, missing_docs)]
+    use tonic::codegen::*;
+    pub struct MapClient<T> {
+        inner: tonic::client::Grpc<T>,
+    }
+    impl MapClient<tonic::transport::Channel> {
+        #[doc = r" Attempt to create a new client by connecting to a given endpoint."]
+        pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
+        where
+            D: std::convert::TryInto<tonic::transport::Endpoint>,
+            D::Error: Into<StdError>,
+        {
+            let conn = tonic::transport::Endpoint::new(dst)?.connect().await?;
+            Ok(Self::new(conn))
+        }
+    }
+    impl<T> MapClient<T>
+    where
+        T: tonic::client::GrpcService<tonic::body::BoxBody>,
+        T::ResponseBody: Body + HttpBody + Send + 'static,
+        T::Error: Into<StdError>,
+        <T::ResponseBody as HttpBody>::Error: Into<StdError> + Send,
+    {
+        pub fn new(inner: T) -> Self {
+            let inner = tonic::client::Grpc::new(inner);
+            Self { inner }
+        }
+        pub fn with_interceptor(inner: T, interceptor: impl Into<tonic::Interceptor>) -> Self {
+            let inner = tonic::client::Grpc::with_interceptor(inner, interceptor);
+            Self { inner }
+        }
+        pub async fn get_map(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetMapPayload>,
+        ) -> Result<tonic::Response<super::GetMapResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/pursuit.api.mortalkin.Map/GetMap");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+    }
+    impl<T: Clone> Clone for MapClient<T> {
+        fn clone(&self) -> Self {
+            Self {
+                inner: self.inner.clone(),
+            }
+        }
+    }
+    impl<T> std::fmt::Debug for MapClient<T> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "MapClient {{ ... }}")
+        }
+    }
+}
+#[doc = r" Generated server implementations."]
+pub mod map_server {
+    #![allow(unused_variables, dead_code, missing_docs)]
+    use tonic::codegen::*;
+    #[doc = "Generated trait containing gRPC methods that should be implemented for use with MapServer."]
+    #[async_trait]
+    pub trait Map: Send + Sync + 'static {
+        async fn get_map(
+            &self,
+            request: tonic::Request<super::GetMapPayload>,
+        ) -> Result<tonic::Response<super::GetMapResponse>, tonic::Status>;
+    }
+    #[derive(Debug)]
+    pub struct MapServer<T: Map> {
+        inner: _Inner<T>,
+    }
+    struct _Inner<T>(Arc<T>, Option<tonic::Interceptor>);
+    impl<T: Map> MapServer<T> {
+        pub fn new(inner: T) -> Self {
+            let inner = Arc::new(inner);
+            let inner = _Inner(inner, None);
+            Self { inner }
+        }
+        pub fn with_interceptor(inner: T, interceptor: impl Into<tonic::Interceptor>) -> Self {
+            let inner = Arc::new(inner);
+            let inner = _Inner(inner, Some(interceptor.into()));
+            Self { inner }
+        }
+    }
+    impl<T, B> Service<http::Request<B>> for MapServer<T>
+    where
+        T: Map,
+        B: HttpBody + Send + Sync + 'static,
+        B::Error: Into<StdError> + Send + 'static,
+    {
+        type Response = http::Response<tonic::body::BoxBody>;
+        type Error = Never;
+        type Future = BoxFuture<Self::Response, Self::Error>;
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+        fn call(&mut self, req: http::Request<B>) -> Self::Future {
+            let inner = self.inner.clone();
+            match req.uri().path() {
+                "/pursuit.api.mortalkin.Map/GetMap" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetMapSvc<T: Map>(pub Arc<T>);
+                    impl<T: Map> tonic::server::UnaryService<super::GetMapPayload> for GetMapSvc<T> {
+                        type Response = super::GetMapResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetMapPayload>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).get_map(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = GetMapSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                _ => Box::pin(async move {
+                    Ok(http::Response::builder()
+                        .status(200)
+                        .header("grpc-status", "12")
+                        .header("content-type", "application/grpc")
+                        .body(tonic::body::BoxBody::empty())
+                        .unwrap())
+                }),
+            }
+        }
+    }
+    impl<T: Map> Clone for MapServer<T> {
+        fn clone(&self) -> Self {
+            let inner = self.inner.clone();
+            Self { inner }
+        }
+    }
+    impl<T: Map> Clone for _Inner<T> {
+        fn clone(&self) -> Self {
+            Self(self.0.clone(), self.1.clone())
+        }
+    }
+    impl<T: std::fmt::Debug> std::fmt::Debug for _Inner<T> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{:?}", self.0)
+        }
+    }
+    impl<T: Map> tonic::transport::NamedService for MapServer<T> {
+        const NAME: &'static str = "pursuit.api.mortalkin.Map";
+    }
+}
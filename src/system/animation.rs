@@ -0,0 +1,354 @@
+use std::collections::HashMap;
+
+use super::movement::Motion;
+
+use bevy::{
+    asset::LoadState,
+    prelude::*,
+    render::texture::{Extent3d, TextureDimension},
+    sprite::TextureAtlasBuilder,
+};
+
+// The red hat boy frames are 669x569, they're shrunk to this height when the
+// atlas is built so all of them fit the default 2048 square.
+const FRAME_HEIGHT: u32 = 64;
+// Brings him to roughly a tile and a half tall.
+pub const CHARACTER_SCALE: f32 = 0.08 * 569. / FRAME_HEIGHT as f32;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Clip {
+    Idle,
+    Run,
+    Jump,
+    Hurt,
+    Dead,
+    Slide,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Playback {
+    Loop,
+    // Play through once and hold the last frame.
+    Once,
+}
+
+pub struct ClipDef {
+    clip: Clip,
+    name: &'static str,
+    frame_count: usize,
+    fps: f32,
+    playback: Playback,
+}
+
+const CLIPS: [ClipDef; 6] = [
+    ClipDef {
+        clip: Clip::Idle,
+        name: "Idle",
+        frame_count: 10,
+        fps: 10.,
+        playback: Playback::Loop,
+    },
+    ClipDef {
+        clip: Clip::Run,
+        name: "Run",
+        frame_count: 8,
+        fps: 15.,
+        playback: Playback::Loop,
+    },
+    ClipDef {
+        clip: Clip::Jump,
+        name: "Jump",
+        frame_count: 12,
+        fps: 20.,
+        playback: Playback::Once,
+    },
+    ClipDef {
+        clip: Clip::Hurt,
+        name: "Hurt",
+        frame_count: 8,
+        fps: 16.,
+        playback: Playback::Once,
+    },
+    ClipDef {
+        clip: Clip::Dead,
+        name: "Dead",
+        frame_count: 10,
+        fps: 12.,
+        playback: Playback::Once,
+    },
+    ClipDef {
+        clip: Clip::Slide,
+        name: "Slide",
+        frame_count: 5,
+        fps: 12.,
+        playback: Playback::Once,
+    },
+];
+
+fn clip_def(clip: Clip) -> &'static ClipDef {
+    CLIPS.iter().find(|def| def.clip == clip).unwrap()
+}
+
+#[derive(Default)]
+pub struct CharacterAnimations {
+    handles: Vec<Handle<Texture>>,
+    pub atlas: Handle<TextureAtlas>,
+    // Atlas indices of every frame, in playing order.
+    frames: HashMap<Clip, Vec<u32>>,
+    pub loaded: bool,
+}
+
+impl CharacterAnimations {
+    pub fn first_frame(&self, clip: Clip) -> u32 {
+        self.frames[&clip][0]
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Facing {
+    Left,
+    Right,
+}
+
+pub struct AnimationState {
+    pub clip: Clip,
+    pub facing: Facing,
+    frame: usize,
+    elapsed: f32,
+    last_point: Vec2,
+    // The clip stays on its last frame until the character moves, like
+    // sitting down.
+    held: bool,
+    // Seconds a one-shot clip keeps starting over for, like dancing.
+    repeat: f32,
+}
+
+impl AnimationState {
+    pub fn new(point: Vec2) -> Self {
+        Self {
+            clip: Clip::Idle,
+            facing: Facing::Right,
+            frame: 0,
+            elapsed: 0.,
+            last_point: point,
+            held: false,
+            repeat: 0.,
+        }
+    }
+
+    pub fn play(&mut self, clip: Clip) {
+        // Restarting a looping clip would make it stutter, a finished one-shot
+        // clip plays again though.
+        if self.clip == clip && !self.is_finished() {
+            return;
+        }
+
+        self.clip = clip;
+        self.frame = 0;
+        self.elapsed = 0.;
+        self.held = false;
+        self.repeat = 0.;
+    }
+
+    pub fn hold(&mut self, clip: Clip) {
+        self.play(clip);
+        self.held = true;
+    }
+
+    pub fn repeat(&mut self, clip: Clip, seconds: f32) {
+        self.play(clip);
+        self.repeat = seconds;
+    }
+
+    pub fn is_finished(&self) -> bool {
+        let def = clip_def(self.clip);
+        def.playback == Playback::Once && self.frame + 1 >= def.frame_count
+    }
+}
+
+// Sent by anything outside of movement that wants a character to play a clip,
+// e.g. getting hit or dying.
+pub struct PlayClip {
+    pub entity: Entity,
+    pub clip: Clip,
+}
+
+pub fn setup(mut animations: ResMut<CharacterAnimations>, asset_server: Res<AssetServer>) {
+    for def in CLIPS.iter() {
+        for n in 1..=def.frame_count {
+            let path = format!("sprite/red hat boy/{} ({}).png", def.name, n);
+            animations.handles.push(asset_server.load(path.as_str()));
+        }
+    }
+}
+
+// Averages every block of pixels into one, weighted by alpha so the
+// transparent background doesn't darken the outline. Frames are RGBA8.
+fn downscale(texture: &Texture, height: u32) -> Texture {
+    let width = (texture.size.width * height / texture.size.height).max(1);
+    let source_width = texture.size.width as usize;
+    let source_height = texture.size.height as usize;
+    let mut data = Vec::with_capacity((width * height * 4) as usize);
+    for y in 0..height as usize {
+        let y0 = y * source_height / height as usize;
+        let y1 = ((y + 1) * source_height / height as usize).max(y0 + 1);
+        for x in 0..width as usize {
+            let x0 = x * source_width / width as usize;
+            let x1 = ((x + 1) * source_width / width as usize).max(x0 + 1);
+
+            let mut sum = [0u32; 4];
+            for sy in y0..y1 {
+                for sx in x0..x1 {
+                    let pixel = &texture.data[(sy * source_width + sx) * 4..][..4];
+                    let alpha = pixel[3] as u32;
+                    for (total, value) in sum.iter_mut().zip(&pixel[..3]) {
+                        *total += *value as u32 * alpha;
+                    }
+                    sum[3] += alpha;
+                }
+            }
+            let count = ((y1 - y0) * (x1 - x0)) as u32;
+            data.extend(
+                sum[..3]
+                    .iter()
+                    .map(|total| total.checked_div(sum[3]).unwrap_or(0) as u8),
+            );
+            data.push((sum[3] / count) as u8);
+        }
+    }
+
+    Texture::new(
+        Extent3d::new(width, height, 1),
+        TextureDimension::D2,
+        data,
+        texture.format,
+    )
+}
+
+pub fn build_atlas(
+    mut animations: ResMut<CharacterAnimations>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    mut textures: ResMut<Assets<Texture>>,
+    asset_server: Res<AssetServer>,
+) {
+    if animations.loaded {
+        return;
+    }
+
+    if let LoadState::Loaded =
+        asset_server.get_group_load_state(animations.handles.iter().map(|handle| handle.id))
+    {
+        // The full size frames are dropped once the small ones are in.
+        let handles: Vec<Handle<Texture>> = animations
+            .handles
+            .iter()
+            .map(|handle| {
+                let small = downscale(textures.get(handle).unwrap(), FRAME_HEIGHT);
+                textures.add(small)
+            })
+            .collect();
+        animations.handles = handles;
+
+        let mut texture_atlas_builder = TextureAtlasBuilder::default();
+        for handle in animations.handles.iter() {
+            let texture = textures.get(handle).unwrap();
+            texture_atlas_builder.add_texture(handle.clone_weak(), &texture);
+        }
+        let texture_atlas = texture_atlas_builder.finish(&mut textures).unwrap();
+
+        let mut frames = HashMap::new();
+        let mut handles = animations.handles.iter();
+        for def in CLIPS.iter() {
+            let indices = handles
+                .by_ref()
+                .take(def.frame_count)
+                .map(|handle| texture_atlas.get_texture_index(handle).unwrap() as u32)
+                .collect();
+            frames.insert(def.clip, indices);
+        }
+
+        animations.frames = frames;
+        animations.atlas = texture_atlases.add(texture_atlas);
+        animations.loaded = true;
+    }
+}
+
+pub fn play_events(mut events: EventReader<PlayClip>, mut query: Query<&mut AnimationState>) {
+    for event in events.iter() {
+        if let Ok(mut state) = query.get_mut(event.entity) {
+            state.play(event.clip);
+        }
+    }
+}
+
+// Runs after the movement step, a tween that ends is followed by the next one
+// in the same frame so running doesn't drop to idle at every tile.
+pub fn drive_from_movement(mut query: Query<(&Motion, &mut AnimationState)>) {
+    for (motion, mut state) in query.iter_mut() {
+        let point = motion.current();
+        let delta = point - state.last_point;
+        state.last_point = point;
+        // Free movement has no tween, the point changing is all there is.
+        let moving = motion.is_moving() || delta != Vec2::ZERO;
+
+        if delta.x < 0. {
+            state.facing = Facing::Left;
+        } else if delta.x > 0. {
+            state.facing = Facing::Right;
+        }
+
+        // Walking away ends an emote.
+        if moving {
+            state.held = false;
+            state.repeat = 0.;
+        }
+        if state.held {
+            continue;
+        }
+        if state.repeat > 0. && state.is_finished() {
+            state.frame = 0;
+            state.elapsed = 0.;
+            continue;
+        }
+
+        // One-shot clips get to finish before walking takes over again, and
+        // the dead stay dead.
+        let def = clip_def(state.clip);
+        if state.clip == Clip::Dead || (def.playback == Playback::Once && !state.is_finished()) {
+            continue;
+        }
+
+        if moving {
+            state.play(Clip::Run);
+        } else {
+            state.play(Clip::Idle);
+        }
+    }
+}
+
+pub fn animate(
+    time: Res<Time>,
+    animations: Res<CharacterAnimations>,
+    mut query: Query<(&mut AnimationState, &mut TextureAtlasSprite)>,
+) {
+    if !animations.loaded {
+        return;
+    }
+
+    for (mut state, mut sprite) in query.iter_mut() {
+        let def = clip_def(state.clip);
+        state.repeat = (state.repeat - time.delta_seconds()).max(0.);
+        state.elapsed += time.delta_seconds();
+        while state.elapsed >= 1. / def.fps {
+            state.elapsed -= 1. / def.fps;
+            if state.frame + 1 < def.frame_count {
+                state.frame += 1;
+            } else if def.playback == Playback::Loop {
+                state.frame = 0;
+            }
+        }
+
+        sprite.index = animations.frames[&state.clip][state.frame];
+        sprite.flip_x = state.facing == Facing::Left;
+    }
+}
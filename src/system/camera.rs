@@ -0,0 +1,192 @@
+use super::chat::Chat;
+use super::minimap::WorldMap;
+use super::GameMap;
+use super::TILE_SIZE;
+
+use bevy::{
+    input::mouse::MouseWheel,
+    prelude::*,
+    render::camera::{Camera, CameraProjection, OrthographicProjection},
+};
+
+const ZOOM_STEP: f32 = 0.1;
+// Pixels the camera moves at full trauma.
+const MAX_SHAKE: f32 = 12.;
+// Trauma lost per second.
+const SHAKE_DECAY: f32 = 1.5;
+
+pub struct FieldCamera {
+    // Where the camera looks before shake is added on top.
+    focus: Vec2,
+}
+
+// The entity the field camera follows, normally the local player.
+pub struct CameraTarget;
+
+pub struct CameraRig {
+    // Higher catches up with the target faster.
+    pub smoothing: f32,
+    pub zoom: f32,
+    pub min_zoom: f32,
+    pub max_zoom: f32,
+    trauma: f32,
+    // Jump to the target on the next frame, e.g. when it was moved across
+    // the map.
+    snap: bool,
+}
+
+impl Default for CameraRig {
+    fn default() -> Self {
+        Self {
+            smoothing: 8.,
+            zoom: 1.,
+            min_zoom: 0.5,
+            max_zoom: 2.,
+            trauma: 0.,
+            snap: false,
+        }
+    }
+}
+
+impl CameraRig {
+    pub fn snap(&mut self) {
+        self.snap = true;
+    }
+}
+
+// Hook for anything that wants the screen to shake, e.g. taking a big hit.
+// Trauma adds up and is capped at 1.
+pub struct ShakeCamera {
+    pub trauma: f32,
+}
+
+pub fn spawn(mut commands: Commands) {
+    commands
+        .spawn_bundle(OrthographicCameraBundle::new_2d())
+        .insert(FieldCamera { focus: Vec2::ZERO });
+}
+
+pub fn zoom(
+    mut rig: ResMut<CameraRig>,
+    mut mouse_wheel_events: EventReader<MouseWheel>,
+    keyboard_input: Res<Input<KeyCode>>,
+    world_map: Res<WorldMap>,
+    chat: Res<Chat>,
+) {
+    // The wheel belongs to the world map while it's open.
+    if world_map.open {
+        return;
+    }
+
+    let mut steps = 0.;
+    for event in mouse_wheel_events.iter() {
+        steps -= event.y.signum();
+    }
+    // Keys typed into the chat aren't for the camera.
+    if !chat.typing {
+        if keyboard_input.just_pressed(KeyCode::Equals)
+            || keyboard_input.just_pressed(KeyCode::NumpadAdd)
+        {
+            steps -= 1.;
+        }
+        if keyboard_input.just_pressed(KeyCode::Minus)
+            || keyboard_input.just_pressed(KeyCode::NumpadSubtract)
+        {
+            steps += 1.;
+        }
+    }
+
+    rig.zoom = (rig.zoom + steps * ZOOM_STEP)
+        .max(rig.min_zoom)
+        .min(rig.max_zoom);
+}
+
+pub fn shake(mut rig: ResMut<CameraRig>, mut events: EventReader<ShakeCamera>) {
+    for event in events.iter() {
+        rig.trauma = (rig.trauma + event.trauma).min(1.);
+    }
+}
+
+pub fn follow(
+    time: Res<Time>,
+    windows: Res<Windows>,
+    game_state: Res<GameMap>,
+    mut rig: ResMut<CameraRig>,
+    target_query: Query<&Transform, (With<CameraTarget>, Without<FieldCamera>)>,
+    new_target_query: Query<&Transform, (Added<CameraTarget>, Without<FieldCamera>)>,
+    mut camera_query: Query<(
+        &mut FieldCamera,
+        &mut Transform,
+        &mut OrthographicProjection,
+        &mut Camera,
+    )>,
+) {
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    let delta = time.delta_seconds();
+
+    for (mut field_camera, mut transform, mut projection, mut camera) in camera_query.iter_mut() {
+        if (projection.scale - rig.zoom).abs() > f32::EPSILON {
+            let blend = 1. - (-rig.smoothing * delta).exp();
+            projection.scale += (rig.zoom - projection.scale) * blend;
+            if (projection.scale - rig.zoom).abs() < 0.001 {
+                projection.scale = rig.zoom;
+            }
+            // Bevy only rebuilds the projection on resize, so do it here.
+            camera.projection_matrix = projection.get_projection_matrix();
+        }
+
+        if let Some(target) = target_query.iter().next() {
+            let blend = 1. - (-rig.smoothing * delta).exp();
+            let goal = target.translation.truncate();
+            field_camera.focus += (goal - field_camera.focus) * blend;
+        }
+        // A new target, e.g. after entering a zone, is jumped to rather than
+        // panned over to.
+        if let Some(target) = new_target_query.iter().next() {
+            field_camera.focus = target.translation.truncate();
+        }
+        if rig.snap {
+            if let Some(target) = target_query.iter().next() {
+                field_camera.focus = target.translation.truncate();
+            }
+        }
+
+        if game_state.map_loaded {
+            let data = &game_state.data;
+            let half_view = Vec2::new(window.width(), window.height()) / 2. * projection.scale;
+            let min = Vec2::new(data.min_x() as f32, data.min_y() as f32) * TILE_SIZE
+                - Vec2::splat(TILE_SIZE / 2.);
+            let max = min + Vec2::new(data.width as f32, data.height as f32) * TILE_SIZE;
+            field_camera.focus.x = clamp_axis(field_camera.focus.x, min.x, max.x, half_view.x);
+            field_camera.focus.y = clamp_axis(field_camera.focus.y, min.y, max.y, half_view.y);
+        }
+
+        // Two out of phase waves are enough to look random without pulling
+        // in an rng.
+        let seconds = time.seconds_since_startup() as f32;
+        let amount = rig.trauma * rig.trauma * MAX_SHAKE;
+        let offset = Vec2::new(
+            (seconds * 47.).sin() * amount,
+            (seconds * 61. + 1.3).sin() * amount,
+        );
+
+        transform.translation.x = field_camera.focus.x + offset.x;
+        transform.translation.y = field_camera.focus.y + offset.y;
+    }
+
+    rig.trauma = (rig.trauma - SHAKE_DECAY * delta).max(0.);
+    rig.snap = false;
+}
+
+// Keeps the view inside the map, or centred on it when the map is smaller
+// than the window.
+fn clamp_axis(value: f32, min: f32, max: f32, half_view: f32) -> f32 {
+    if max - min <= half_view * 2. {
+        return (min + max) / 2.;
+    }
+
+    value.max(min + half_view).min(max - half_view)
+}
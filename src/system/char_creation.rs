@@ -212,6 +212,10 @@ pub fn submit_system(
                         id: character.id,
                         name: character.name.clone(),
                         position: None,
+                        zone_id: character
+                            .position
+                            .as_ref()
+                            .map_or(0, |position| position.zone_id),
                     });
                     app_state.set(AppState::CharSelectionMenu).unwrap();
                 }
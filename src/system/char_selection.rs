@@ -228,6 +228,11 @@ pub fn play_button_system(
                         token: token.token.clone(),
                         character_id: play_button.character.id,
                         position: None,
+                        enter_portal: None,
+                        chat: None,
+                        emote: None,
+                        interact: None,
+                        attack: None,
                     })
                     .unwrap();
 
@@ -0,0 +1,541 @@
+use std::collections::VecDeque;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::emote::{self, UseEmote};
+use super::picking::UiPanel;
+use super::Character;
+use super::Player;
+use super::RequestSender;
+use super::Token;
+use super::TILE_SIZE;
+use crate::pursuit::api::mortalkin::{self, ChatChannel, ChatMessage, EmoteKind};
+
+use bevy::{prelude::*, text::Text2dSize};
+
+// Lines kept for scrolling back, and how many the box shows at once.
+const MAX_LINES: usize = 200;
+const VISIBLE_LINES: usize = 10;
+const MAX_LENGTH: usize = 200;
+const FONT_SIZE: f32 = 16.;
+const BOX_WIDTH: f32 = 440.;
+// Over the nameplates, under the fog.
+const BUBBLE_Z: f32 = 195.;
+// Bottom of the bubble, above the name.
+const BUBBLE_OFFSET: f32 = TILE_SIZE * 1.5;
+const BUBBLE_SECONDS: f32 = 5.;
+const BUBBLE_FONT_SIZE: f32 = 13.;
+const BUBBLE_LINE_CHARS: usize = 28;
+const BUBBLE_MAX_CHARS: usize = 120;
+const BUBBLE_PADDING: f32 = 6.;
+
+const TIME_COLOUR: Color = Color::rgb(0.6, 0.6, 0.6);
+
+fn channel_colour(channel: ChatChannel) -> Color {
+    match channel {
+        ChatChannel::Say => Color::WHITE,
+        ChatChannel::Zone => Color::rgb(1., 0.7, 0.4),
+        ChatChannel::Whisper => Color::rgb(1., 0.5, 0.9),
+        ChatChannel::System => Color::rgb(1., 0.9, 0.3),
+    }
+}
+
+// Hours and minutes, in UTC since there's no time zone to go by.
+fn clock(millis: u64) -> String {
+    let minutes = millis / 1000 / 60;
+    format!("{:02}:{:02}", minutes / 60 % 24, minutes % 60)
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+// What a line typed into the chat turned out to be.
+enum Typed {
+    Message(ChatMessage),
+    Emote(EmoteKind),
+}
+
+struct ChatLine {
+    channel: ChatChannel,
+    time: String,
+    text: String,
+}
+
+pub struct Chat {
+    // The input line is open, keys go to it rather than the field.
+    pub typing: bool,
+    input: String,
+    // Channel of lines typed without a command, changed with /s and /z.
+    channel: ChatChannel,
+    // Last player who whispered us, for /r.
+    reply_to: Option<String>,
+    lines: VecDeque<ChatLine>,
+    // Lines scrolled back from the newest one.
+    scroll: usize,
+    dirty: bool,
+    // Players who just said something, waiting for their speech bubble.
+    bubbles: Vec<(u32, String)>,
+    font: Handle<Font>,
+    bubble_material: Handle<ColorMaterial>,
+}
+
+impl FromWorld for Chat {
+    fn from_world(world: &mut World) -> Self {
+        let font = {
+            let asset_server = world.get_resource::<AssetServer>().unwrap();
+            asset_server.load("fonts/FiraSans-Medium.otf")
+        };
+        let mut materials = world.get_resource_mut::<Assets<ColorMaterial>>().unwrap();
+        Self {
+            typing: false,
+            input: String::new(),
+            channel: ChatChannel::Say,
+            reply_to: None,
+            lines: VecDeque::new(),
+            scroll: 0,
+            dirty: true,
+            bubbles: Vec::new(),
+            font,
+            bubble_material: materials.add(Color::rgba(0., 0., 0., 0.7).into()),
+        }
+    }
+}
+
+impl Chat {
+    fn push(&mut self, channel: ChatChannel, millis: u64, text: String) {
+        self.lines.push_back(ChatLine {
+            channel,
+            time: clock(millis),
+            text,
+        });
+        if self.lines.len() > MAX_LINES {
+            self.lines.pop_front();
+        }
+        // Someone scrolled back keeps reading the same lines.
+        if self.scroll > 0 {
+            self.scroll = (self.scroll + 1).min(self.lines.len().saturating_sub(VISIBLE_LINES));
+        }
+        self.dirty = true;
+    }
+
+    // A line from the client itself, like a mistyped command.
+    pub fn system(&mut self, text: String) {
+        self.push(ChatChannel::System, now_millis(), text);
+    }
+
+    // Someone did an emote, e.g. "Kin waves." with a bubble saying "o/".
+    pub fn action(&mut self, player_id: u32, name: &str, action: &str, icon: &str) {
+        self.bubbles.push((player_id, icon.to_string()));
+        self.push(
+            ChatChannel::Say,
+            now_millis(),
+            format!("{} {}.", name, action),
+        );
+    }
+
+    pub fn receive(&mut self, message: ChatMessage, our_id: u32) {
+        let channel = ChatChannel::from_i32(message.channel).unwrap_or(ChatChannel::System);
+        let text = match channel {
+            ChatChannel::Say => format!("{}: {}", message.from_name, message.text),
+            ChatChannel::Zone => format!("[Zone] {}: {}", message.from_name, message.text),
+            ChatChannel::Whisper if message.from_id == our_id => {
+                format!("To {}: {}", message.to_name, message.text)
+            }
+            ChatChannel::Whisper => {
+                self.reply_to = Some(message.from_name.clone());
+                format!("From {}: {}", message.from_name, message.text)
+            }
+            ChatChannel::System => message.text.clone(),
+        };
+        if message.from_id != 0 && channel != ChatChannel::Whisper {
+            self.bubbles.push((message.from_id, message.text));
+        }
+
+        let sent_at = if message.sent_at != 0 {
+            message.sent_at
+        } else {
+            now_millis()
+        };
+        self.push(channel, sent_at, text);
+    }
+
+    // Turns a typed line into what to send, if anything.
+    fn command(&mut self, line: &str) -> Option<Typed> {
+        let line = line.trim();
+        if line.is_empty() {
+            return None;
+        }
+        let message = |channel: ChatChannel, to_name: &str, text: &str| {
+            Typed::Message(ChatMessage {
+                channel: channel as i32,
+                to_name: to_name.to_string(),
+                text: text.to_string(),
+                ..Default::default()
+            })
+        };
+        if !line.starts_with('/') {
+            return Some(message(self.channel, "", line));
+        }
+
+        let mut parts = line.splitn(2, ' ');
+        let command = parts.next().unwrap();
+        let rest = parts.next().unwrap_or("").trim();
+        match command {
+            "/s" | "/say" | "/z" | "/zone" => {
+                self.channel = if command.starts_with("/s") {
+                    ChatChannel::Say
+                } else {
+                    ChatChannel::Zone
+                };
+                Some(message(self.channel, "", rest)).filter(|_| !rest.is_empty())
+            }
+            "/w" | "/whisper" => {
+                let mut parts = rest.splitn(2, ' ');
+                let name = parts.next().unwrap_or("");
+                let text = parts.next().unwrap_or("").trim();
+                if name.is_empty() || text.is_empty() {
+                    self.system(format!("Usage: {} name message", command));
+                    return None;
+                }
+                Some(message(ChatChannel::Whisper, name, text))
+            }
+            "/r" | "/reply" => match self.reply_to.clone() {
+                Some(name) if !rest.is_empty() => Some(message(ChatChannel::Whisper, &name, rest)),
+                Some(_) => None,
+                None => {
+                    self.system("Nobody whispered you yet.".to_string());
+                    None
+                }
+            },
+            _ => match emote::from_command(command) {
+                Some(kind) => Some(Typed::Emote(kind)),
+                None => {
+                    self.system(format!("Unknown command {}.", command));
+                    None
+                }
+            },
+        }
+    }
+}
+
+pub struct ChatHistory;
+pub struct ChatInput;
+
+// Shown over a player for a while after they spoke, the text is a child of
+// its background.
+pub struct SpeechBubble {
+    player: Entity,
+    timer: Timer,
+}
+
+// Messages from the server, ours included.
+pub fn receive(
+    current_char: Res<Character>,
+    mut events: EventReader<ChatMessage>,
+    mut chat: ResMut<Chat>,
+) {
+    for message in events.iter() {
+        chat.receive(message.clone(), current_char.id);
+    }
+}
+
+pub fn setup(
+    mut commands: Commands,
+    chat: Res<Chat>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let text_style = Style {
+        max_size: Size::new(Val::Px(BOX_WIDTH - 16.), Val::Undefined),
+        margin: Rect::all(Val::Px(4.)),
+        ..Default::default()
+    };
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(10.),
+                    bottom: Val::Px(10.),
+                    ..Default::default()
+                },
+                size: Size::new(Val::Px(BOX_WIDTH), Val::Auto),
+                // Children go from the bottom up, the input line first.
+                flex_direction: FlexDirection::Column,
+                ..Default::default()
+            },
+            material: materials.add(Color::rgba(0., 0., 0., 0.4).into()),
+            ..Default::default()
+        })
+        .insert(UiPanel)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    style: text_style.clone(),
+                    text: Text::with_section(
+                        "",
+                        TextStyle {
+                            font: chat.font.clone(),
+                            font_size: FONT_SIZE,
+                            color: Color::WHITE,
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                })
+                .insert(ChatInput);
+            parent
+                .spawn_bundle(TextBundle {
+                    style: text_style,
+                    text: Text::default(),
+                    ..Default::default()
+                })
+                .insert(ChatHistory);
+        });
+}
+
+// Enter opens the input line and sends it, Escape drops it. Page up and down
+// scroll through older lines.
+pub fn input(
+    mut chat: ResMut<Chat>,
+    mut char_input_events: EventReader<ReceivedCharacter>,
+    keyboard_input: Res<Input<KeyCode>>,
+    request_sender: Res<RequestSender>,
+    token: Res<Token>,
+    current_char: Res<Character>,
+    mut emote_events: EventWriter<UseEmote>,
+) {
+    for event in char_input_events.iter() {
+        match event.char {
+            '\r' if !chat.typing => chat.typing = true,
+            '\r' => {
+                let line = std::mem::take(&mut chat.input);
+                chat.typing = false;
+                match chat.command(&line) {
+                    Some(Typed::Message(message)) => request_sender
+                        .tx
+                        .lock()
+                        .unwrap()
+                        .unbounded_send(mortalkin::PlayGamePayload {
+                            token: token.token.clone(),
+                            character_id: current_char.id,
+                            position: None,
+                            enter_portal: None,
+                            chat: Some(message),
+                            emote: None,
+                            interact: None,
+                            attack: None,
+                        })
+                        .unwrap(),
+                    Some(Typed::Emote(kind)) => emote_events.send(UseEmote(kind)),
+                    None => {}
+                }
+            }
+            _ if !chat.typing => continue,
+            '\x08' => {
+                chat.input.pop();
+            }
+            c if c.is_control() => continue,
+            c => {
+                if chat.input.chars().count() < MAX_LENGTH {
+                    chat.input.push(c);
+                }
+            }
+        }
+        chat.dirty = true;
+    }
+
+    if chat.typing && keyboard_input.just_pressed(KeyCode::Escape) {
+        chat.typing = false;
+        chat.input.clear();
+        chat.dirty = true;
+    }
+
+    let oldest = chat.lines.len().saturating_sub(VISIBLE_LINES);
+    if keyboard_input.just_pressed(KeyCode::PageUp) {
+        chat.scroll = (chat.scroll + VISIBLE_LINES / 2).min(oldest);
+        chat.dirty = true;
+    }
+    if keyboard_input.just_pressed(KeyCode::PageDown) {
+        chat.scroll = chat.scroll.saturating_sub(VISIBLE_LINES / 2);
+        chat.dirty = true;
+    }
+}
+
+pub fn refresh(
+    mut chat: ResMut<Chat>,
+    mut history_query: Query<&mut Text, (With<ChatHistory>, Without<ChatInput>)>,
+    mut input_query: Query<&mut Text, With<ChatInput>>,
+) {
+    if !chat.dirty {
+        return;
+    }
+    chat.dirty = false;
+
+    let end = chat.lines.len() - chat.scroll;
+    let start = end.saturating_sub(VISIBLE_LINES);
+    let style = |color: Color| TextStyle {
+        font: chat.font.clone(),
+        font_size: FONT_SIZE,
+        color,
+    };
+    let mut sections = Vec::new();
+    for line in chat.lines.range(start..end) {
+        if !sections.is_empty() {
+            sections.push(TextSection {
+                value: "\n".to_string(),
+                style: style(TIME_COLOUR),
+            });
+        }
+        sections.push(TextSection {
+            value: format!("{} ", line.time),
+            style: style(TIME_COLOUR),
+        });
+        sections.push(TextSection {
+            value: line.text.clone(),
+            style: style(channel_colour(line.channel)),
+        });
+    }
+    if chat.scroll > 0 {
+        sections.push(TextSection {
+            value: format!("\n({} newer)", chat.scroll),
+            style: style(TIME_COLOUR),
+        });
+    }
+    for mut text in history_query.iter_mut() {
+        text.sections = sections.clone();
+    }
+
+    for mut text in input_query.iter_mut() {
+        let section = &mut text.sections[0];
+        section.value = if chat.typing {
+            let channel = match chat.channel {
+                ChatChannel::Zone => "Zone",
+                _ => "Say",
+            };
+            format!("{}: {}_", channel, chat.input)
+        } else {
+            String::new()
+        };
+        section.style.color = channel_colour(chat.channel);
+    }
+}
+
+fn wrap(text: &str) -> String {
+    let cut = text.chars().count() > BUBBLE_MAX_CHARS;
+    let mut text: String = text.chars().take(BUBBLE_MAX_CHARS).collect();
+    if cut {
+        text.push_str("...");
+    }
+    let mut wrapped = String::new();
+    let mut line_chars = 0;
+    for word in text.split_whitespace() {
+        let word_chars = word.chars().count();
+        if line_chars > 0 && line_chars + 1 + word_chars > BUBBLE_LINE_CHARS {
+            wrapped.push('\n');
+            line_chars = 0;
+        } else if line_chars > 0 {
+            wrapped.push(' ');
+            line_chars += 1;
+        }
+        wrapped.push_str(word);
+        line_chars += word_chars;
+    }
+    wrapped
+}
+
+// A player only has one bubble, saying something else replaces it.
+pub fn spawn_bubbles(
+    mut commands: Commands,
+    mut chat: ResMut<Chat>,
+    player_query: Query<(Entity, &Player)>,
+    bubble_query: Query<(Entity, &SpeechBubble)>,
+) {
+    let bubbles = std::mem::take(&mut chat.bubbles);
+    for (player_id, text) in bubbles {
+        let player = match player_query
+            .iter()
+            .find(|(_, player)| player.id == player_id)
+        {
+            Some((entity, _)) => entity,
+            None => continue,
+        };
+        for (entity, bubble) in bubble_query.iter() {
+            if bubble.player == player {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite::new(Vec2::ZERO),
+                material: chat.bubble_material.clone(),
+                ..Default::default()
+            })
+            .insert(SpeechBubble {
+                player,
+                timer: Timer::from_seconds(BUBBLE_SECONDS, false),
+            })
+            .with_children(|parent| {
+                parent.spawn_bundle(Text2dBundle {
+                    text: Text::with_section(
+                        wrap(&text),
+                        TextStyle {
+                            font: chat.font.clone(),
+                            font_size: BUBBLE_FONT_SIZE,
+                            color: Color::WHITE,
+                        },
+                        TextAlignment {
+                            vertical: VerticalAlign::Center,
+                            horizontal: HorizontalAlign::Center,
+                        },
+                    ),
+                    transform: Transform::from_translation(Vec3::new(0., 0., 0.1)),
+                    ..Default::default()
+                });
+            });
+    }
+}
+
+// Bubbles sit over their player, sized to their text, until they time out
+// or the player goes away.
+pub fn update_bubbles(
+    mut commands: Commands,
+    time: Res<Time>,
+    player_query: Query<&Transform, With<Player>>,
+    mut bubble_query: Query<
+        (
+            Entity,
+            &mut SpeechBubble,
+            &Children,
+            &mut Sprite,
+            &mut Transform,
+        ),
+        Without<Player>,
+    >,
+    size_query: Query<&Text2dSize>,
+) {
+    for (entity, mut bubble, children, mut sprite, mut transform) in bubble_query.iter_mut() {
+        bubble.timer.tick(time.delta());
+        let player_transform = match player_query.get(bubble.player) {
+            Ok(player_transform) if !bubble.timer.finished() => player_transform,
+            _ => {
+                commands.entity(entity).despawn_recursive();
+                continue;
+            }
+        };
+
+        let text_size = children
+            .iter()
+            .find_map(|child| size_query.get(*child).ok())
+            .map_or(Vec2::ZERO, |size| {
+                Vec2::new(size.size.width, size.size.height)
+            });
+        sprite.size = text_size + Vec2::splat(BUBBLE_PADDING * 2.);
+        let point = player_transform.translation.truncate()
+            + Vec2::new(0., BUBBLE_OFFSET + sprite.size.y / 2.);
+        transform.translation = point.extend(BUBBLE_Z);
+    }
+}
@@ -0,0 +1,211 @@
+use std::collections::HashSet;
+
+use super::Character;
+use super::GameMap;
+use super::Player;
+use super::Position;
+
+use bevy::prelude::*;
+
+// Marks an entity that occupies its tile, so nobody else can walk into it.
+// Remote players get it on spawn; leave it out for anything that should be
+// walked through.
+pub struct Blocking;
+
+#[derive(Default, Clone)]
+pub struct CollisionMap {
+    min_x: i32,
+    min_y: i32,
+    width: i32,
+    height: i32,
+    walkable: Vec<bool>,
+    blockers: HashSet<(i32, i32)>,
+}
+
+impl CollisionMap {
+    pub fn new(min_x: i32, min_y: i32, width: i32, height: i32) -> Self {
+        Self {
+            min_x,
+            min_y,
+            width,
+            height,
+            walkable: vec![true; (width * height) as usize],
+            blockers: HashSet::new(),
+        }
+    }
+
+    pub fn in_bounds(&self, x: i32, y: i32) -> bool {
+        x >= self.min_x
+            && y >= self.min_y
+            && x < self.min_x + self.width
+            && y < self.min_y + self.height
+    }
+
+    fn index(&self, x: i32, y: i32) -> usize {
+        ((y - self.min_y) * self.width + (x - self.min_x)) as usize
+    }
+
+    pub fn set_walkable(&mut self, x: i32, y: i32, walkable: bool) {
+        if !self.in_bounds(x, y) {
+            return;
+        }
+
+        let index = self.index(x, y);
+        self.walkable[index] = walkable;
+    }
+
+    pub fn set_blockers(&mut self, blockers: impl Iterator<Item = (i32, i32)>) {
+        self.blockers.clear();
+        self.blockers.extend(blockers);
+    }
+
+    pub fn is_walkable(&self, x: i32, y: i32) -> bool {
+        self.in_bounds(x, y) && self.walkable[self.index(x, y)] && !self.blockers.contains(&(x, y))
+    }
+
+    // Only the map itself, players standing around don't block anything.
+    pub fn is_wall(&self, x: i32, y: i32) -> bool {
+        self.in_bounds(x, y) && !self.walkable[self.index(x, y)]
+    }
+
+    pub fn can_move(&self, from: Position, delta_xy: (i32, i32)) -> bool {
+        if !self.is_walkable(from.x + delta_xy.0, from.y + delta_xy.1) {
+            return false;
+        }
+
+        // Squeezing diagonally between two blocked tiles is not allowed, but
+        // cutting a single corner is.
+        if delta_xy.0 != 0 && delta_xy.1 != 0 {
+            let horizontal = self.is_walkable(from.x + delta_xy.0, from.y);
+            let vertical = self.is_walkable(from.x, from.y + delta_xy.1);
+            return horizontal || vertical;
+        }
+
+        true
+    }
+}
+
+pub fn sync_blockers(
+    current_char: Res<Character>,
+    mut game_state: ResMut<GameMap>,
+    query: Query<(&Position, Option<&Player>), With<Blocking>>,
+) {
+    let blockers = query
+        .iter()
+        .filter(|(_, player)| player.map_or(true, |player| player.id != current_char.id))
+        .map(|(position, _)| (position.x, position.y));
+
+    game_state.collision.set_blockers(blockers);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A 4x4 map starting at (0, 0) with a wall at (1, 0) and (0, 1).
+    fn map() -> CollisionMap {
+        let mut collision = CollisionMap::new(0, 0, 4, 4);
+        collision.set_walkable(1, 0, false);
+        collision.set_walkable(0, 1, false);
+        collision
+    }
+
+    fn at(x: i32, y: i32) -> Position {
+        Position { x, y }
+    }
+
+    #[test]
+    fn blocked_tile() {
+        let collision = map();
+        assert!(!collision.can_move(at(2, 0), (-1, 0)));
+        assert!(collision.can_move(at(2, 0), (1, 0)));
+    }
+
+    #[test]
+    fn map_edge() {
+        let collision = map();
+        assert!(!collision.in_bounds(-1, 0));
+        assert!(!collision.in_bounds(4, 3));
+        assert!(!collision.can_move(at(3, 3), (1, 0)));
+        assert!(!collision.can_move(at(3, 3), (0, 1)));
+        assert!(!collision.can_move(at(2, 0), (0, -1)));
+    }
+
+    #[test]
+    fn diagonal_one_corner() {
+        let collision = map();
+        // Only (1, 0) is in the way, (2, 1) is open.
+        assert!(collision.can_move(at(1, 1), (1, -1)));
+        assert!(collision.can_move(at(2, 1), (-1, 1)));
+    }
+
+    #[test]
+    fn diagonal_both_corners() {
+        let collision = map();
+        assert!(collision.is_walkable(0, 0));
+        assert!(collision.is_walkable(1, 1));
+        assert!(!collision.can_move(at(0, 0), (1, 1)));
+        assert!(!collision.can_move(at(1, 1), (-1, -1)));
+    }
+
+    #[test]
+    fn blockers() {
+        let mut collision = map();
+        collision.set_blockers(vec![(2, 2)].into_iter());
+        assert!(!collision.is_walkable(2, 2));
+        assert!(!collision.is_wall(2, 2));
+        assert!(!collision.can_move(at(2, 1), (0, 1)));
+
+        collision.set_blockers(Vec::new().into_iter());
+        assert!(collision.can_move(at(2, 1), (0, 1)));
+    }
+
+    #[test]
+    fn blocking_entities() {
+        let mut world = World::default();
+        world.insert_resource(Character {
+            id: 1,
+            name: String::new(),
+            position: None,
+            zone_id: 0,
+        });
+        world.insert_resource(GameMap {
+            collision: map(),
+            ..Default::default()
+        });
+        world
+            .spawn()
+            .insert(Player {
+                id: 1,
+                ..Default::default()
+            })
+            .insert(at(2, 2))
+            .insert(Blocking);
+        world
+            .spawn()
+            .insert(Player {
+                id: 2,
+                ..Default::default()
+            })
+            .insert(at(3, 2))
+            .insert(Blocking);
+        world.spawn().insert(at(2, 3)).insert(Blocking);
+        world.spawn().insert(at(3, 3));
+
+        let mut stage = SystemStage::single(sync_blockers.system());
+        stage.run(&mut world);
+
+        let mut game_state = world.get_resource_mut::<GameMap>().unwrap();
+        // We don't block ourselves, everyone else with `Blocking` does.
+        assert!(game_state.collision.is_walkable(2, 2));
+        assert!(!game_state.collision.is_walkable(3, 2));
+        assert!(!game_state.collision.is_walkable(2, 3));
+        assert!(game_state.collision.is_walkable(3, 3));
+
+        let mut position = at(2, 2);
+        assert!(!game_state.try_move_player(&mut position, (1, 0)));
+        assert!(position == at(2, 2));
+        assert!(game_state.try_move_player(&mut position, (-1, 0)));
+        assert!(position == at(1, 2));
+    }
+}
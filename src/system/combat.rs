@@ -0,0 +1,441 @@
+use super::animation::{AnimationState, Clip, Facing, PlayClip};
+use super::camera::ShakeCamera;
+use super::chat::Chat;
+use super::collision::Blocking;
+use super::death::Respawn;
+use super::dialogue::Dialogue;
+use super::emote::Emotes;
+use super::mob::{self, Mob};
+use super::movement::Motion;
+use super::picking::{Hover, UiPanel};
+use super::target::Target;
+use super::Character;
+use super::Health;
+use super::Player;
+use super::Position;
+use super::RequestSender;
+use super::Token;
+use super::TILE_SIZE;
+use crate::pursuit::api::mortalkin::{self, Fighter, FighterKind};
+
+use bevy::prelude::*;
+
+// Seconds between two attacks, the server has the final say.
+const ATTACK_COOLDOWN: f64 = 1.;
+// Over the chat bubbles, under the fog.
+const NUMBER_Z: f32 = 197.;
+const NUMBER_OFFSET: f32 = TILE_SIZE;
+const NUMBER_FONT_SIZE: f32 = 18.;
+const NUMBER_SECONDS: f32 = 1.;
+// Pixels per second the numbers float up.
+const NUMBER_SPEED: f32 = 30.;
+const FLASH_SECONDS: f32 = 0.2;
+const CORPSE_SECONDS: f32 = 1.;
+const FRAME_WIDTH: f32 = 220.;
+const BAR_HEIGHT: f32 = 12.;
+const FRAME_FONT_SIZE: f32 = 18.;
+
+// Damage we took, damage we did and everybody else's.
+const TAKEN_COLOUR: Color = Color::rgb(1., 0.3, 0.3);
+const DEALT_COLOUR: Color = Color::rgb(1., 0.9, 0.4);
+const OTHER_COLOUR: Color = Color::rgb(0.85, 0.85, 0.85);
+
+// A character or mob whose hit points ran out. Characters lie there until they
+// respawn, mobs fade away.
+pub struct Dead;
+
+pub struct DamageNumber {
+    age: f32,
+}
+
+// Seconds a mob stays tinted red after a hit, it has no hurt animation.
+pub struct HitFlash(f32);
+
+pub struct Corpse {
+    age: f32,
+}
+
+pub struct Combat {
+    // When we can attack again, in seconds since startup.
+    ready_at: f64,
+    font: Handle<Font>,
+}
+
+impl FromWorld for Combat {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world.get_resource::<AssetServer>().unwrap();
+        Self {
+            ready_at: 0.,
+            font: asset_server.load("fonts/FiraSans-Bold.otf"),
+        }
+    }
+}
+
+fn is_us(fighter: &Option<Fighter>, character_id: u32) -> bool {
+    match fighter {
+        Some(fighter) => {
+            fighter.kind == FighterKind::Character as i32 && fighter.id == character_id
+        }
+        None => false,
+    }
+}
+
+fn find_fighter(
+    fighter: &Option<Fighter>,
+    player_query: &Query<(Entity, &Player)>,
+    mob_query: &Query<(Entity, &Mob)>,
+) -> Option<(Entity, String)> {
+    let fighter = fighter.as_ref()?;
+    match FighterKind::from_i32(fighter.kind)? {
+        FighterKind::Character => player_query
+            .iter()
+            .find(|(_, player)| player.id == fighter.id)
+            .map(|(entity, player)| (entity, player.name.clone())),
+        FighterKind::Mob => mob_query
+            .iter()
+            .find(|(_, mob)| mob.id == fighter.id)
+            .map(|(entity, mob)| (entity, mob::mob_def(mob.kind).name.to_string())),
+    }
+}
+
+// F or right clicking on a mob attacks the target when it's next to us.
+pub fn attack(
+    time: Res<Time>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mouse_input: Res<Input<MouseButton>>,
+    hover: Res<Hover>,
+    mut chat: ResMut<Chat>,
+    dialogue: Res<Dialogue>,
+    emotes: Res<Emotes>,
+    current_char: Res<Character>,
+    request_sender: Res<RequestSender>,
+    token: Res<Token>,
+    mut combat: ResMut<Combat>,
+    mut target: ResMut<Target>,
+    mut player_query: Query<(&Player, &Position, &mut AnimationState), Without<Dead>>,
+    mob_query: Query<(&Mob, &Position), Without<Dead>>,
+) {
+    if chat.typing || dialogue.is_open() || emotes.menu_open {
+        return;
+    }
+    let clicked = mouse_input.just_pressed(MouseButton::Right) && hover.mob.is_some();
+    if clicked {
+        target.entity = hover.mob;
+    }
+    if !clicked && !keyboard_input.just_pressed(KeyCode::F) {
+        return;
+    }
+
+    let (ours, mut state) = match player_query
+        .iter_mut()
+        .find(|(player, ..)| player.id == current_char.id)
+    {
+        Some((_, position, state)) => (*position, state),
+        None => return,
+    };
+    let (mob, position) = match target.entity.and_then(|entity| mob_query.get(entity).ok()) {
+        Some(found) => found,
+        None => {
+            chat.system("There's nothing to attack.".to_string());
+            return;
+        }
+    };
+    if (position.x - ours.x).abs() > 1 || (position.y - ours.y).abs() > 1 {
+        chat.system("Too far away.".to_string());
+        return;
+    }
+    let now = time.seconds_since_startup();
+    if now < combat.ready_at {
+        return;
+    }
+    combat.ready_at = now + ATTACK_COOLDOWN;
+
+    if position.x < ours.x {
+        state.facing = Facing::Left;
+    } else if position.x > ours.x {
+        state.facing = Facing::Right;
+    }
+
+    request_sender
+        .tx
+        .lock()
+        .unwrap()
+        .unbounded_send(mortalkin::PlayGamePayload {
+            token: token.token.clone(),
+            character_id: current_char.id,
+            position: None,
+            enter_portal: None,
+            chat: None,
+            emote: None,
+            interact: None,
+            attack: Some(mortalkin::Attack {
+                target: Some(Fighter {
+                    kind: FighterKind::Mob as i32,
+                    id: mob.id,
+                }),
+            }),
+        })
+        .unwrap();
+}
+
+// Shows the hits and deaths the server sent: hit points, damage numbers,
+// the hurt and dead clips for characters and a red flash for mobs.
+pub fn apply(
+    mut commands: Commands,
+    time: Res<Time>,
+    current_char: Res<Character>,
+    mut damage: EventReader<mortalkin::Damage>,
+    mut deaths: EventReader<mortalkin::Death>,
+    mut respawn: ResMut<Respawn>,
+    combat: Res<Combat>,
+    mut chat: ResMut<Chat>,
+    mut clips: EventWriter<PlayClip>,
+    mut shakes: EventWriter<ShakeCamera>,
+    player_query: Query<(Entity, &Player)>,
+    mob_query: Query<(Entity, &Mob)>,
+    mut fighter_query: Query<(&Motion, Option<&mut Health>)>,
+) {
+    for hit in damage.iter() {
+        let entity = match find_fighter(&hit.target, &player_query, &mob_query) {
+            Some((entity, _)) => entity,
+            None => continue,
+        };
+        let (motion, health) = match fighter_query.get_mut(entity) {
+            Ok(found) => found,
+            Err(_) => continue,
+        };
+
+        let hp = Health {
+            current: hit.hp,
+            max: hit.max_hp,
+        };
+        match health {
+            Some(mut health) => *health = hp,
+            None => {
+                commands.entity(entity).insert(hp);
+            }
+        }
+
+        let colour = if is_us(&hit.target, current_char.id) {
+            shakes.send(ShakeCamera { trauma: 0.3 });
+            TAKEN_COLOUR
+        } else if is_us(&hit.source, current_char.id) {
+            DEALT_COLOUR
+        } else {
+            OTHER_COLOUR
+        };
+        let point = motion.current() + Vec2::new(0., NUMBER_OFFSET);
+        commands
+            .spawn_bundle(Text2dBundle {
+                text: Text::with_section(
+                    hit.amount.to_string(),
+                    TextStyle {
+                        font: combat.font.clone(),
+                        font_size: NUMBER_FONT_SIZE,
+                        color: colour,
+                    },
+                    TextAlignment {
+                        vertical: VerticalAlign::Center,
+                        horizontal: HorizontalAlign::Center,
+                    },
+                ),
+                transform: Transform::from_translation(point.extend(NUMBER_Z)),
+                ..Default::default()
+            })
+            .insert(DamageNumber { age: 0. });
+
+        // The killing blow plays the dead clip instead.
+        if hit.hp == 0 {
+            continue;
+        }
+        if player_query.get(entity).is_ok() {
+            clips.send(PlayClip {
+                entity,
+                clip: Clip::Hurt,
+            });
+        } else {
+            commands.entity(entity).insert(HitFlash(FLASH_SECONDS));
+        }
+    }
+
+    for death in deaths.iter() {
+        let (entity, name) = match find_fighter(&death.fighter, &player_query, &mob_query) {
+            Some(found) => found,
+            None => continue,
+        };
+        commands.entity(entity).insert(Dead);
+        if player_query.get(entity).is_ok() {
+            clips.send(PlayClip {
+                entity,
+                clip: Clip::Dead,
+            });
+        } else {
+            commands
+                .entity(entity)
+                .remove::<Blocking>()
+                .insert(Corpse { age: 0. });
+        }
+
+        if is_us(&death.fighter, current_char.id) {
+            respawn.start(time.seconds_since_startup() + death.respawn_seconds as f64);
+            let killer = find_fighter(&death.killer, &player_query, &mob_query)
+                .map_or("something".to_string(), |(_, name)| name);
+            chat.system(format!("You were killed by {}.", killer));
+        } else if is_us(&death.killer, current_char.id) {
+            chat.system(format!("You defeated {}.", name));
+        }
+    }
+}
+
+// Damage numbers float up and fade, hit mobs turn back to their colour and
+// dead ones fade away.
+pub fn fade(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut number_query: Query<(Entity, &mut DamageNumber, &mut Transform, &mut Text)>,
+    mut flash_query: Query<(Entity, &mut HitFlash, &mut TextureAtlasSprite), Without<Corpse>>,
+    mut corpse_query: Query<(Entity, &mut Corpse, &mut TextureAtlasSprite)>,
+) {
+    let delta = time.delta_seconds();
+    for (entity, mut number, mut transform, mut text) in number_query.iter_mut() {
+        number.age += delta;
+        if number.age >= NUMBER_SECONDS {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        transform.translation.y += NUMBER_SPEED * delta;
+        text.sections[0]
+            .style
+            .color
+            .set_a(1. - number.age / NUMBER_SECONDS);
+    }
+
+    for (entity, mut flash, mut sprite) in flash_query.iter_mut() {
+        flash.0 -= delta;
+        if flash.0 <= 0. {
+            sprite.color = Color::WHITE;
+            commands.entity(entity).remove::<HitFlash>();
+        } else {
+            sprite.color = Color::rgb(1., 0.4, 0.4);
+        }
+    }
+
+    for (entity, mut corpse, mut sprite) in corpse_query.iter_mut() {
+        corpse.age += delta;
+        if corpse.age >= CORPSE_SECONDS {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        sprite.color = Color::rgba(1., 1., 1., 1. - corpse.age / CORPSE_SECONDS);
+    }
+}
+
+pub struct PlayerFrame;
+pub struct PlayerFrameText;
+pub struct PlayerFrameBar;
+pub struct PlayerFrameFill;
+
+// Our name and hit points, in the top left corner under the frame rate.
+pub fn setup(
+    mut commands: Commands,
+    combat: Res<Combat>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(10.),
+                    top: Val::Px(80.),
+                    ..Default::default()
+                },
+                size: Size::new(Val::Px(FRAME_WIDTH), Val::Auto),
+                // Children go from the bottom up, the bar first.
+                flex_direction: FlexDirection::Column,
+                padding: Rect::all(Val::Px(6.)),
+                ..Default::default()
+            },
+            material: materials.add(Color::rgba(0., 0., 0., 0.6).into()),
+            ..Default::default()
+        })
+        .insert(PlayerFrame)
+        .insert(UiPanel)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Percent(100.), Val::Px(BAR_HEIGHT)),
+                        ..Default::default()
+                    },
+                    material: materials.add(Color::rgba(0.2, 0.05, 0.05, 1.).into()),
+                    ..Default::default()
+                })
+                .insert(PlayerFrameBar)
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle(NodeBundle {
+                            style: Style {
+                                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                                ..Default::default()
+                            },
+                            material: materials.add(Color::rgb(0.3, 0.8, 0.3).into()),
+                            ..Default::default()
+                        })
+                        .insert(PlayerFrameFill);
+                });
+            parent
+                .spawn_bundle(TextBundle {
+                    style: Style {
+                        margin: Rect {
+                            bottom: Val::Px(4.),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    },
+                    text: Text::with_section(
+                        "",
+                        TextStyle {
+                            font: combat.font.clone(),
+                            font_size: FRAME_FONT_SIZE,
+                            color: Color::WHITE,
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                })
+                .insert(PlayerFrameText);
+        });
+}
+
+pub fn update_player_frame(
+    current_char: Res<Character>,
+    player_query: Query<(&Player, Option<&Health>)>,
+    mut text_query: Query<&mut Text, With<PlayerFrameText>>,
+    mut fill_query: Query<&mut Style, With<PlayerFrameFill>>,
+) {
+    let health = player_query
+        .iter()
+        .find(|(player, _)| player.id == current_char.id)
+        .and_then(|(_, health)| health.copied());
+
+    let label = match health {
+        Some(health) if health.max != 0 => {
+            format!("{}  {}/{}", current_char.name, health.current, health.max)
+        }
+        _ => current_char.name.clone(),
+    };
+    for mut text in text_query.iter_mut() {
+        if text.sections[0].value != label {
+            text.sections[0].value = label.clone();
+        }
+    }
+
+    let ratio = match health {
+        Some(health) if health.max != 0 => health.current as f32 / health.max as f32,
+        _ => 1.,
+    };
+    for mut style in fill_query.iter_mut() {
+        style.size.width = Val::Percent(ratio * 100.);
+    }
+}
@@ -0,0 +1,284 @@
+use std::sync::{mpsc, Mutex};
+
+use super::animation::{AnimationState, Clip};
+use super::camera::CameraRig;
+use super::chat::Chat;
+use super::combat::Dead;
+use super::movement::Motion;
+use super::zone::ZoneTransition;
+use super::Character;
+use super::Health;
+use super::Player;
+use super::Position;
+use super::Token;
+use crate::pursuit::api::mortalkin::{RespawnPayload, RespawnResponse};
+
+use bevy::prelude::*;
+
+const TITLE_FONT_SIZE: f32 = 48.;
+const FONT_SIZE: f32 = 22.;
+
+pub struct RespawnRequestSender {
+    pub tx: Mutex<mpsc::Sender<RespawnPayload>>,
+}
+
+pub struct RespawnResponseReceiver {
+    pub rx: Mutex<mpsc::Receiver<Result<tonic::Response<RespawnResponse>, tonic::Status>>>,
+}
+
+#[derive(Default)]
+pub struct Respawn {
+    // While we're dead, when we can ask to respawn in seconds since startup.
+    ready_at: Option<f64>,
+    // Waiting for the server to answer.
+    requested: bool,
+}
+
+impl Respawn {
+    pub fn start(&mut self, ready_at: f64) {
+        self.ready_at = Some(ready_at);
+        self.requested = false;
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.ready_at.is_some()
+    }
+}
+
+pub struct DeathOverlay;
+pub struct DeathTitle;
+pub struct DeathCountdown;
+
+pub fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let hidden = Visible {
+        is_visible: false,
+        is_transparent: true,
+    };
+    let font = asset_server.load("fonts/FiraSans-Bold.otf");
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                // Children go from the bottom up, the countdown first.
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: materials.add(Color::rgba(0.25, 0., 0., 0.45).into()),
+            visible: hidden.clone(),
+            ..Default::default()
+        })
+        .insert(DeathOverlay)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        "",
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: FONT_SIZE,
+                            color: Color::WHITE,
+                        },
+                        Default::default(),
+                    ),
+                    visible: hidden.clone(),
+                    ..Default::default()
+                })
+                .insert(DeathCountdown);
+            parent
+                .spawn_bundle(TextBundle {
+                    style: Style {
+                        margin: Rect {
+                            bottom: Val::Px(12.),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    },
+                    text: Text::with_section(
+                        "You died",
+                        TextStyle {
+                            font,
+                            font_size: TITLE_FONT_SIZE,
+                            color: Color::rgb(0.9, 0.2, 0.2),
+                        },
+                        Default::default(),
+                    ),
+                    visible: hidden,
+                    ..Default::default()
+                })
+                .insert(DeathTitle);
+        });
+}
+
+// Counts down to the respawn while we're dead, R asks the server once it's
+// over.
+pub fn countdown(
+    time: Res<Time>,
+    keyboard_input: Res<Input<KeyCode>>,
+    chat: Res<Chat>,
+    token: Res<Token>,
+    current_char: Res<Character>,
+    request_sender: Res<RespawnRequestSender>,
+    mut respawn: ResMut<Respawn>,
+    mut text_query: Query<&mut Text, With<DeathCountdown>>,
+    mut visible_query: Query<
+        &mut Visible,
+        Or<(With<DeathOverlay>, With<DeathTitle>, With<DeathCountdown>)>,
+    >,
+) {
+    for mut visible in visible_query.iter_mut() {
+        if visible.is_visible != respawn.is_dead() {
+            visible.is_visible = respawn.is_dead();
+        }
+    }
+    let ready_at = match respawn.ready_at {
+        Some(ready_at) => ready_at,
+        None => return,
+    };
+
+    let left = ready_at - time.seconds_since_startup();
+    let label = if respawn.requested {
+        "Respawning...".to_string()
+    } else if left > 0. {
+        format!("Respawn in {:.0}s", left.ceil())
+    } else {
+        "Press R to respawn".to_string()
+    };
+    for mut text in text_query.iter_mut() {
+        if text.sections[0].value != label {
+            text.sections[0].value = label.clone();
+        }
+    }
+
+    if left > 0. || respawn.requested || chat.typing {
+        return;
+    }
+    if keyboard_input.just_pressed(KeyCode::R) {
+        request_sender
+            .tx
+            .lock()
+            .unwrap()
+            .send(RespawnPayload {
+                token: token.token.clone(),
+                character_id: current_char.id,
+            })
+            .unwrap();
+        respawn.requested = true;
+    }
+}
+
+// The camera stays on our corpse until the server tells where we're back,
+// then jumps there with us instead of panning across the map.
+pub fn respawn(
+    mut commands: Commands,
+    current_char: Res<Character>,
+    response_receiver: Res<RespawnResponseReceiver>,
+    mut respawn: ResMut<Respawn>,
+    mut chat: ResMut<Chat>,
+    mut rig: ResMut<CameraRig>,
+    mut transition: ResMut<ZoneTransition>,
+    mut query: Query<(
+        Entity,
+        &Player,
+        &mut Position,
+        &mut Motion,
+        &mut Transform,
+        &mut AnimationState,
+        Option<&mut Health>,
+    )>,
+) {
+    let response = match response_receiver.rx.lock().unwrap().try_recv() {
+        Ok(response) => response,
+        Err(_) => return,
+    };
+    let placement = match response {
+        Ok(response) => response.into_inner(),
+        Err(status) => {
+            chat.system(format!("Can't respawn: {}", status.message()));
+            respawn.requested = false;
+            return;
+        }
+    };
+    // Without a position there's nowhere to put us, we stay dead and can ask
+    // again.
+    let (zone_id, position) = match placement.position {
+        Some(position) => (
+            position.zone_id,
+            Position {
+                x: position.x,
+                y: position.y,
+            },
+        ),
+        None => {
+            chat.system("Can't respawn: the server didn't say where.".to_string());
+            respawn.requested = false;
+            return;
+        }
+    };
+    *respawn = Respawn::default();
+    let health = Health {
+        current: placement.hp,
+        max: placement.max_hp,
+    };
+    // The new zone is built with us standing at the spawn point.
+    if zone_id != current_char.zone_id {
+        transition.start(zone_id, position);
+        return;
+    }
+
+    for (entity, player, mut current, mut motion, mut transform, mut state, current_health) in
+        query.iter_mut()
+    {
+        if player.id != current_char.id {
+            continue;
+        }
+
+        let point = position.to_world();
+        *current = position;
+        *motion = Motion::at(point);
+        transform.translation = point.extend(super::y_sort(point));
+        state.play(Clip::Idle);
+        match current_health {
+            Some(mut current_health) => *current_health = health,
+            None => {
+                commands.entity(entity).insert(health);
+            }
+        }
+        commands.entity(entity).remove::<Dead>();
+        rig.snap();
+    }
+}
+
+// Other players who are back on their feet appear at their spawn point
+// rather than walking there from their corpse.
+pub fn revive(
+    mut commands: Commands,
+    current_char: Res<Character>,
+    mut query: Query<
+        (
+            Entity,
+            &Player,
+            &Position,
+            &Health,
+            &mut Motion,
+            &mut AnimationState,
+        ),
+        With<Dead>,
+    >,
+) {
+    for (entity, player, position, health, mut motion, mut state) in query.iter_mut() {
+        if player.id == current_char.id || health.current == 0 {
+            continue;
+        }
+
+        *motion = Motion::at(position.to_world());
+        state.play(Clip::Idle);
+        commands.entity(entity).remove::<Dead>();
+    }
+}
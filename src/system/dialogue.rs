@@ -0,0 +1,707 @@
+use std::collections::HashMap;
+use std::fs;
+
+use super::chat::Chat;
+use super::picking::UiPanel;
+use super::Character;
+use super::GameMap;
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+const DIALOGUE_DIR: &str = "assets/dialogue";
+const VARIABLES_DIR: &str = "cache/dialogue";
+const CHARS_PER_SECOND: f32 = 40.;
+// The text box art is 112x32, drawn 6 times as big.
+const BOX_SCALE: f32 = 6.;
+const BOX_PADDING: f32 = 30.;
+const FONT_SIZE: f32 = 20.;
+
+const SPEAKER_COLOUR: Color = Color::rgb(1., 0.85, 0.3);
+const TEXT_COLOUR: Color = Color::rgb(0.15, 0.1, 0.1);
+const CHOICE_COLOUR: Color = Color::rgb(0.35, 0.3, 0.3);
+const SELECTED_COLOUR: Color = Color::rgb(0.7, 0.2, 0.1);
+
+// Variables that aren't set yet are 0.
+#[derive(Clone, Deserialize)]
+pub enum Condition {
+    Is(String, i32),
+    AtLeast(String, i32),
+    Below(String, i32),
+}
+
+#[derive(Clone, Deserialize)]
+pub enum Effect {
+    Set(String, i32),
+    Add(String, i32),
+}
+
+#[derive(Clone, Deserialize)]
+pub struct Choice {
+    pub text: String,
+    // The dialogue ends after choices that don't go anywhere.
+    #[serde(default)]
+    pub next: Option<String>,
+    // The choice is only offered when all of them hold.
+    #[serde(default)]
+    pub conditions: Vec<Condition>,
+    #[serde(default)]
+    pub effects: Vec<Effect>,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct Branch {
+    pub conditions: Vec<Condition>,
+    pub next: String,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct Node {
+    // Defaults to the name of whoever we're talking to.
+    #[serde(default)]
+    pub speaker: Option<String>,
+    // `{variable}` is replaced with its value. A node without text only
+    // decides where to go.
+    #[serde(default)]
+    pub text: String,
+    // The first branch whose conditions hold is followed as soon as the node
+    // is reached, before its text is shown.
+    #[serde(default)]
+    pub branches: Vec<Branch>,
+    // Applied when the node is shown.
+    #[serde(default)]
+    pub effects: Vec<Effect>,
+    #[serde(default)]
+    pub choices: Vec<Choice>,
+    // Where a node without choices continues, it ends the dialogue otherwise.
+    #[serde(default)]
+    pub next: Option<String>,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct DialogueTree {
+    pub start: String,
+    pub nodes: HashMap<String, Node>,
+}
+
+impl DialogueTree {
+    pub fn load(name: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = fs::read_to_string(format!("{}/{}.ron", DIALOGUE_DIR, name))?;
+        Ok(ron::de::from_str(&content)?)
+    }
+
+    // A single line and nothing to choose, like what a sign says.
+    pub fn line(text: &str) -> Self {
+        let node = Node {
+            speaker: None,
+            text: text.to_string(),
+            branches: Vec::new(),
+            effects: Vec::new(),
+            choices: Vec::new(),
+            next: None,
+        };
+        let mut nodes = HashMap::new();
+        nodes.insert("line".to_string(), node);
+        Self {
+            start: "line".to_string(),
+            nodes,
+        }
+    }
+}
+
+struct Running {
+    tree: DialogueTree,
+    node: String,
+    speaker: String,
+    text: String,
+    // Characters of `text` shown so far.
+    shown: f32,
+    // Indices of the node's choices that are offered.
+    choices: Vec<usize>,
+    selected: usize,
+    // Keys pressed on the frame the dialogue opened were for opening it.
+    fresh: bool,
+}
+
+pub struct Dialogue {
+    running: Option<Running>,
+    // Kept on disk per character, so choices are remembered.
+    variables: HashMap<String, i32>,
+    character_id: u32,
+    // Whoever we're talking to, the default speaker.
+    partner: String,
+    // Variables changed since they were last saved.
+    changed: bool,
+}
+
+impl Default for Dialogue {
+    fn default() -> Self {
+        Self {
+            running: None,
+            variables: HashMap::new(),
+            character_id: 0,
+            partner: String::new(),
+            changed: false,
+        }
+    }
+}
+
+impl Dialogue {
+    fn path(character_id: u32) -> String {
+        format!("{}/{}.ron", VARIABLES_DIR, character_id)
+    }
+
+    pub fn load(character_id: u32) -> Self {
+        let variables = fs::read_to_string(Self::path(character_id))
+            .ok()
+            .and_then(|content| ron::de::from_str(&content).ok())
+            .unwrap_or_default();
+        Self {
+            variables,
+            character_id,
+            ..Default::default()
+        }
+    }
+
+    fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let content =
+            ron::ser::to_string_pretty(&self.variables, ron::ser::PrettyConfig::default())?;
+        fs::create_dir_all(VARIABLES_DIR)?;
+        fs::write(Self::path(self.character_id), content)?;
+        Ok(())
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.running.is_some()
+    }
+
+    pub fn start(&mut self, tree: DialogueTree, partner: &str) {
+        let start = tree.start.clone();
+        self.partner = partner.to_string();
+        self.running = Some(Running {
+            tree,
+            node: String::new(),
+            speaker: String::new(),
+            text: String::new(),
+            shown: 0.,
+            choices: Vec::new(),
+            selected: 0,
+            fresh: true,
+        });
+        self.enter(Some(start));
+    }
+
+    pub fn close(&mut self) {
+        self.running = None;
+    }
+
+    fn value(&self, name: &str) -> i32 {
+        *self.variables.get(name).unwrap_or(&0)
+    }
+
+    fn holds(&self, conditions: &[Condition]) -> bool {
+        conditions.iter().all(|condition| match condition {
+            Condition::Is(name, value) => self.value(name) == *value,
+            Condition::AtLeast(name, value) => self.value(name) >= *value,
+            Condition::Below(name, value) => self.value(name) < *value,
+        })
+    }
+
+    fn apply(&mut self, effects: &[Effect]) {
+        self.changed |= !effects.is_empty();
+        for effect in effects.iter() {
+            match effect {
+                Effect::Set(name, value) => {
+                    self.variables.insert(name.clone(), *value);
+                }
+                Effect::Add(name, value) => {
+                    *self.variables.entry(name.clone()).or_insert(0) += value;
+                }
+            }
+        }
+    }
+
+    fn interpolate(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        for (name, value) in self.variables.iter() {
+            text = text.replace(&format!("{{{}}}", name), &value.to_string());
+        }
+        text
+    }
+
+    // Moves to `next`, following branches and text-less nodes, or ends the
+    // dialogue when there's nowhere to go.
+    fn enter(&mut self, mut next: Option<String>) {
+        let tree = match self.running.as_ref() {
+            Some(running) => running.tree.clone(),
+            None => return,
+        };
+        // A loop between nodes without text would never show anything.
+        let mut hops = 0;
+        let node = loop {
+            let name = match next.take() {
+                Some(name) if hops <= tree.nodes.len() => name,
+                _ => {
+                    self.close();
+                    return;
+                }
+            };
+            hops += 1;
+            let node = match tree.nodes.get(&name) {
+                Some(node) => node,
+                None => {
+                    self.close();
+                    return;
+                }
+            };
+
+            if let Some(branch) = node
+                .branches
+                .iter()
+                .find(|branch| self.holds(&branch.conditions))
+            {
+                next = Some(branch.next.clone());
+                continue;
+            }
+            if node.text.is_empty() {
+                next = node.next.clone();
+                continue;
+            }
+            break (name, node);
+        };
+
+        let (name, node) = node;
+        self.apply(&node.effects);
+        let choices = node
+            .choices
+            .iter()
+            .enumerate()
+            .filter(|(_, choice)| self.holds(&choice.conditions))
+            .map(|(index, _)| index)
+            .collect();
+        let speaker = node.speaker.clone().unwrap_or_else(|| self.partner.clone());
+        let text = self.interpolate(&node.text);
+
+        let running = self.running.as_mut().unwrap();
+        running.node = name;
+        running.speaker = speaker;
+        running.text = text;
+        running.shown = 0.;
+        running.choices = choices;
+        running.selected = 0;
+    }
+
+    fn node(&self) -> Option<&Node> {
+        let running = self.running.as_ref()?;
+        running.tree.nodes.get(&running.node)
+    }
+
+    // Takes the selected choice, or goes on from a node without choices.
+    fn confirm(&mut self) {
+        let running = match self.running.as_ref() {
+            Some(running) => running,
+            None => return,
+        };
+        let node = self.node().unwrap();
+        if running.choices.is_empty() {
+            let next = node.next.clone();
+            self.enter(next);
+            return;
+        }
+
+        let choice = node.choices[running.choices[running.selected]].clone();
+        self.apply(&choice.effects);
+        self.enter(choice.next);
+    }
+}
+
+pub struct DialogueUi;
+pub struct SpeakerText;
+pub struct BodyText;
+pub struct ChoicesText {
+    font: Handle<Font>,
+}
+
+pub fn setup(
+    mut commands: Commands,
+    mut dialogue: ResMut<Dialogue>,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    current_char: Res<Character>,
+) {
+    *dialogue = Dialogue::load(current_char.id);
+
+    let hidden = Visible {
+        is_visible: false,
+        is_transparent: true,
+    };
+    let font = asset_server.load("fonts/FiraSans-Medium.otf");
+    let text_style = Style {
+        max_size: Size::new(Val::Px(112. * BOX_SCALE - BOX_PADDING * 2.), Val::Undefined),
+        margin: Rect {
+            bottom: Val::Px(6.),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let text = |colour: Color| {
+        Text::with_section(
+            "",
+            TextStyle {
+                font: font.clone(),
+                font_size: FONT_SIZE,
+                color: colour,
+            },
+            Default::default(),
+        )
+    };
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(20.),
+                    ..Default::default()
+                },
+                size: Size::new(Val::Percent(100.), Val::Auto),
+                justify_content: JustifyContent::Center,
+                ..Default::default()
+            },
+            material: materials.add(Color::NONE.into()),
+            visible: hidden.clone(),
+            ..Default::default()
+        })
+        .insert(DialogueUi)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(ImageBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(112. * BOX_SCALE), Val::Px(32. * BOX_SCALE)),
+                        padding: Rect::all(Val::Px(BOX_PADDING)),
+                        // Children go from the bottom up, packed at the top.
+                        flex_direction: FlexDirection::Column,
+                        justify_content: JustifyContent::FlexEnd,
+                        ..Default::default()
+                    },
+                    material: materials
+                        .add(asset_server.load("ui/generic-rpg-ui-text-box.png").into()),
+                    visible: hidden.clone(),
+                    ..Default::default()
+                })
+                .insert(DialogueUi)
+                .insert(UiPanel)
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle(TextBundle {
+                            style: text_style.clone(),
+                            text: Text::default(),
+                            visible: hidden.clone(),
+                            ..Default::default()
+                        })
+                        .insert(DialogueUi)
+                        .insert(ChoicesText { font: font.clone() });
+                    parent
+                        .spawn_bundle(TextBundle {
+                            style: text_style.clone(),
+                            text: text(TEXT_COLOUR),
+                            visible: hidden.clone(),
+                            ..Default::default()
+                        })
+                        .insert(DialogueUi)
+                        .insert(BodyText);
+                    parent
+                        .spawn_bundle(TextBundle {
+                            style: text_style,
+                            text: text(SPEAKER_COLOUR),
+                            visible: hidden.clone(),
+                            ..Default::default()
+                        })
+                        .insert(DialogueUi)
+                        .insert(SpeakerText);
+                });
+        });
+}
+
+// Space or E shows the whole line, then goes on. Up and down, or the number
+// keys, pick a choice and Escape walks away.
+pub fn input(keyboard_input: Res<Input<KeyCode>>, chat: Res<Chat>, mut dialogue: ResMut<Dialogue>) {
+    let running = match dialogue.running.as_mut() {
+        Some(running) => running,
+        None => return,
+    };
+    if running.fresh {
+        running.fresh = false;
+        return;
+    }
+    if chat.typing {
+        return;
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        dialogue.close();
+        return;
+    }
+    let confirm =
+        keyboard_input.just_pressed(KeyCode::Space) || keyboard_input.just_pressed(KeyCode::E);
+    let length = running.text.chars().count() as f32;
+    if running.shown < length {
+        if confirm {
+            running.shown = length;
+        }
+        return;
+    }
+
+    let count = running.choices.len();
+    if count > 0 {
+        if keyboard_input.just_pressed(KeyCode::Up) || keyboard_input.just_pressed(KeyCode::W) {
+            running.selected = (running.selected + count - 1) % count;
+        }
+        if keyboard_input.just_pressed(KeyCode::Down) || keyboard_input.just_pressed(KeyCode::S) {
+            running.selected = (running.selected + 1) % count;
+        }
+        let keys = [
+            KeyCode::Key1,
+            KeyCode::Key2,
+            KeyCode::Key3,
+            KeyCode::Key4,
+            KeyCode::Key5,
+            KeyCode::Key6,
+            KeyCode::Key7,
+            KeyCode::Key8,
+            KeyCode::Key9,
+        ];
+        for (n, key) in keys.iter().enumerate().take(count) {
+            if keyboard_input.just_pressed(*key) {
+                running.selected = n;
+                dialogue.confirm();
+                return;
+            }
+        }
+    }
+    if confirm {
+        dialogue.confirm();
+    }
+}
+
+// Types the text out and keeps the box in sync with the dialogue.
+pub fn typewrite(
+    time: Res<Time>,
+    game_state: Res<GameMap>,
+    mut chat: ResMut<Chat>,
+    mut dialogue: ResMut<Dialogue>,
+    mut ui_query: Query<&mut Visible, With<DialogueUi>>,
+    mut text_query: QuerySet<(
+        Query<&mut Text, With<SpeakerText>>,
+        Query<&mut Text, With<BodyText>>,
+        Query<(&ChoicesText, &mut Text)>,
+    )>,
+) {
+    // Leaving the zone leaves the conversation too.
+    if !game_state.map_loaded {
+        dialogue.close();
+    }
+    // Remember what was decided as soon as it's decided.
+    if dialogue.changed {
+        dialogue.changed = false;
+        if let Err(err) = dialogue.save() {
+            chat.system(format!("Can't save dialogue progress: {}", err));
+        }
+    }
+
+    let open = dialogue.is_open();
+    for mut visible in ui_query.iter_mut() {
+        if visible.is_visible != open {
+            visible.is_visible = open;
+        }
+    }
+    if !open {
+        return;
+    }
+
+    let (speaker, text, choices) = {
+        let running = dialogue.running.as_mut().unwrap();
+        let length = running.text.chars().count() as f32;
+        running.shown = (running.shown + time.delta_seconds() * CHARS_PER_SECOND).min(length);
+        let text: String = running.text.chars().take(running.shown as usize).collect();
+        let choices = if running.shown >= length {
+            running.choices.clone()
+        } else {
+            Vec::new()
+        };
+        (running.speaker.clone(), text, choices)
+    };
+    let selected = dialogue.running.as_ref().unwrap().selected;
+
+    for mut speaker_text in text_query.q0_mut().iter_mut() {
+        if speaker_text.sections[0].value != speaker {
+            speaker_text.sections[0].value = speaker.clone();
+        }
+    }
+    for mut body_text in text_query.q1_mut().iter_mut() {
+        if body_text.sections[0].value != text {
+            body_text.sections[0].value = text.clone();
+        }
+    }
+
+    // The offered choices once the text is out, the selected one marked.
+    let node = dialogue.node().unwrap();
+    for (choices_text, mut text) in text_query.q2_mut().iter_mut() {
+        let sections: Vec<TextSection> = choices
+            .iter()
+            .enumerate()
+            .map(|(n, index)| TextSection {
+                value: format!(
+                    "{}{}. {}\n",
+                    if n == selected { "> " } else { "   " },
+                    n + 1,
+                    node.choices[*index].text
+                ),
+                style: TextStyle {
+                    font: choices_text.font.clone(),
+                    font_size: FONT_SIZE,
+                    color: if n == selected {
+                        SELECTED_COLOUR
+                    } else {
+                        CHOICE_COLOUR
+                    },
+                },
+            })
+            .collect();
+        let changed = text.sections.len() != sections.len()
+            || text
+                .sections
+                .iter()
+                .zip(sections.iter())
+                .any(|(old, new)| old.value != new.value);
+        if changed {
+            text.sections = sections;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree(content: &str) -> DialogueTree {
+        ron::de::from_str(content).unwrap()
+    }
+
+    fn dialogue(variables: &[(&str, i32)]) -> Dialogue {
+        let mut dialogue = Dialogue::default();
+        for (name, value) in variables.iter() {
+            dialogue.variables.insert(name.to_string(), *value);
+        }
+        dialogue
+    }
+
+    fn node(dialogue: &Dialogue) -> &str {
+        &dialogue.running.as_ref().unwrap().node
+    }
+
+    fn text(dialogue: &Dialogue) -> &str {
+        &dialogue.running.as_ref().unwrap().text
+    }
+
+    const GREETING: &str = r#"(
+        start: "hello",
+        nodes: {
+            "hello": (
+                branches: [(conditions: [AtLeast("met", 1)], next: "again")],
+                next: Some("first"),
+            ),
+            "first": (
+                text: "Welcome.",
+                effects: [Set("met", 1)],
+                choices: [
+                    (text: "Bye.", next: None),
+                    (text: "Gold?", next: Some("gold"), conditions: [AtLeast("gold", 1)]),
+                    (text: "Work?", next: Some("work"), effects: [Add("asked", 1)]),
+                ],
+            ),
+            "again": (text: "Visit number {met}."),
+            "gold": (text: "You have {gold} gold."),
+            "work": (text: "", next: Some("busy")),
+            "busy": (text: "Not now, {name}."),
+        },
+    )"#;
+
+    #[test]
+    fn branches() {
+        let mut first = dialogue(&[]);
+        first.start(tree(GREETING), "Vendor");
+        assert_eq!(node(&first), "first");
+        assert_eq!(first.value("met"), 1);
+
+        let mut again = dialogue(&[("met", 2)]);
+        again.start(tree(GREETING), "Vendor");
+        assert_eq!(node(&again), "again");
+    }
+
+    #[test]
+    fn choices() {
+        let mut dialogue = dialogue(&[]);
+        dialogue.start(tree(GREETING), "Vendor");
+        // The gold choice isn't offered without gold.
+        assert_eq!(dialogue.running.as_ref().unwrap().choices, vec![0, 2]);
+
+        dialogue.running.as_mut().unwrap().selected = 1;
+        dialogue.confirm();
+        assert_eq!(dialogue.value("asked"), 1);
+        assert_eq!(node(&dialogue), "busy");
+
+        dialogue.confirm();
+        assert!(!dialogue.is_open());
+
+        // Choices that don't go anywhere end the dialogue.
+        let mut bye = Dialogue::default();
+        bye.start(tree(GREETING), "Vendor");
+        bye.confirm();
+        assert!(!bye.is_open());
+    }
+
+    #[test]
+    fn text_less_nodes() {
+        let mut greeting = tree(GREETING);
+        greeting.start = "work".to_string();
+        let mut dialogue = dialogue(&[]);
+        dialogue.start(greeting, "Vendor");
+        assert_eq!(node(&dialogue), "busy");
+        assert_eq!(dialogue.running.as_ref().unwrap().speaker, "Vendor");
+    }
+
+    #[test]
+    fn loops() {
+        let looping = tree(
+            r#"(
+                start: "a",
+                nodes: {
+                    "a": (next: Some("b")),
+                    "b": (branches: [(conditions: [], next: "a")]),
+                },
+            )"#,
+        );
+        let mut dialogue = dialogue(&[]);
+        dialogue.start(looping, "Vendor");
+        assert!(!dialogue.is_open());
+    }
+
+    #[test]
+    fn missing_nodes() {
+        let mut dialogue = dialogue(&[]);
+        dialogue.start(tree(r#"(start: "gone", nodes: {})"#), "Vendor");
+        assert!(!dialogue.is_open());
+    }
+
+    #[test]
+    fn interpolation() {
+        let mut dialogue = dialogue(&[("met", 2), ("gold", 15)]);
+        assert_eq!(dialogue.interpolate("{gold} of {gold}"), "15 of 15");
+        // Unknown variables are left as they are.
+        assert_eq!(dialogue.interpolate("Not now, {name}."), "Not now, {name}.");
+
+        dialogue.start(tree(GREETING), "Vendor");
+        assert_eq!(text(&dialogue), "Visit number 2.");
+    }
+}
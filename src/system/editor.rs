@@ -0,0 +1,828 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use super::camera::{CameraTarget, FieldCamera};
+use super::field;
+use super::map::{self, Frame, MapData, MapObject, ObjectKind, PlacedTile, TileKind};
+use super::picking::cursor_to_world;
+use super::Position;
+use super::TileSpriteHandles;
+use super::TILE_SIZE;
+
+use bevy::{prelude::*, render::camera::OrthographicProjection};
+
+use bevy_tilemap::prelude::*;
+
+// Screen pixels per second the view pans at zoom 1.
+const PAN_SPEED: f32 = 600.;
+// A fill never touches more tiles than this, maps can be huge.
+const MAX_FILL: usize = 1 << 16;
+const PALETTE_COLUMNS: usize = 4;
+const PALETTE_TILE: f32 = 32.;
+const MARKER_Z: f32 = 3.;
+
+const UNSELECTED: Color = Color::rgb(0.55, 0.55, 0.55);
+
+// Starts the client in the editor for a zone with `--editor <zone id>`.
+pub fn zone_from_args() -> Option<u32> {
+    let mut args = std::env::args().skip_while(|arg| arg != "--editor");
+    args.next()?;
+    Some(args.next().and_then(|zone| zone.parse().ok()).unwrap_or(0))
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Tool {
+    Brush,
+    Fill,
+    Erase,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Layer {
+    Ground,
+    Collision,
+    Portals,
+    Spawn,
+}
+
+// One tile or object before and after an edit.
+#[derive(Clone)]
+enum Change {
+    // `None` is the map's fill.
+    Tile {
+        point: (i32, i32),
+        before: Option<String>,
+        after: Option<String>,
+    },
+    Blocked {
+        point: (i32, i32),
+        before: bool,
+        after: bool,
+    },
+    Portals {
+        before: Vec<MapObject>,
+        after: Vec<MapObject>,
+    },
+    Spawn {
+        before: (i32, i32),
+        after: (i32, i32),
+    },
+}
+
+pub struct Editor {
+    zone_id: u32,
+    data: MapData,
+    // Working copies of the map's tiles and blocked tiles, written back into
+    // `data` on save.
+    tiles: HashMap<(i32, i32), String>,
+    blocked: HashSet<(i32, i32)>,
+    loaded: bool,
+    tool: Tool,
+    layer: Layer,
+    // Texture painted with, from the palette.
+    selected: Option<String>,
+    // Zone new portals lead to.
+    portal_zone: u32,
+    // Each edit is every change of one click or brush stroke.
+    undo: Vec<Vec<Change>>,
+    redo: Vec<Vec<Change>>,
+    stroke: Vec<Change>,
+    // Tiles that need drawing again, and whether the markers do.
+    redraw: Vec<(i32, i32)>,
+    markers_dirty: bool,
+    unsaved: bool,
+    message: String,
+    marker_materials: HashMap<&'static str, Handle<ColorMaterial>>,
+}
+
+impl Editor {
+    pub fn new(zone_id: u32) -> Self {
+        let mut editor = Self {
+            zone_id,
+            data: MapData::default(),
+            tiles: HashMap::new(),
+            blocked: HashSet::new(),
+            loaded: false,
+            tool: Tool::Brush,
+            layer: Layer::Ground,
+            selected: None,
+            portal_zone: 0,
+            undo: Vec::new(),
+            redo: Vec::new(),
+            stroke: Vec::new(),
+            redraw: Vec::new(),
+            markers_dirty: true,
+            unsaved: false,
+            message: String::new(),
+            marker_materials: HashMap::new(),
+        };
+        editor.read();
+        editor
+    }
+
+    fn path(&self) -> String {
+        map::zone_path(self.zone_id)
+    }
+
+    fn read(&mut self) {
+        self.data = match MapData::load(&self.path()) {
+            Ok(data) => data,
+            Err(err) => {
+                self.message = format!("Can't load {}: {}", self.path(), err);
+                return;
+            }
+        };
+        self.tiles = self
+            .data
+            .tiles
+            .iter()
+            .map(|placed| ((placed.x, placed.y), placed.kind.clone()))
+            .collect();
+        self.blocked = self.data.blocked.iter().cloned().collect();
+        self.undo.clear();
+        self.redo.clear();
+        self.stroke.clear();
+        self.unsaved = false;
+        self.message = format!("Loaded {}", self.path());
+    }
+
+    fn write(&mut self) {
+        // Sorted so saving the same map twice gives the same file.
+        let mut tiles: Vec<PlacedTile> = self
+            .tiles
+            .iter()
+            .map(|((x, y), kind)| PlacedTile {
+                x: *x,
+                y: *y,
+                kind: kind.clone(),
+            })
+            .collect();
+        tiles.sort_by_key(|placed| (placed.y, placed.x));
+        let mut blocked: Vec<(i32, i32)> = self.blocked.iter().cloned().collect();
+        blocked.sort_by_key(|(x, y)| (*y, *x));
+        self.data.tiles = tiles;
+        self.data.blocked = blocked;
+
+        match self.data.save(&self.path()) {
+            Ok(()) => {
+                self.unsaved = false;
+                self.message = format!("Saved {}", self.path());
+            }
+            Err(err) => self.message = format!("Can't save {}: {}", self.path(), err),
+        }
+    }
+
+    fn in_bounds(&self, (x, y): (i32, i32)) -> bool {
+        let data = &self.data;
+        x >= data.min_x()
+            && y >= data.min_y()
+            && x < data.min_x() + data.width
+            && y < data.min_y() + data.height
+    }
+
+    // Kind that draws a texture, made up when the map doesn't have one yet.
+    fn kind_for(&mut self, texture: &str) -> String {
+        let existing = self
+            .data
+            .kinds
+            .iter()
+            .find(|(_, kind)| kind.frames.len() == 1 && kind.frames[0].texture == texture);
+        if let Some((name, _)) = existing {
+            return name.clone();
+        }
+
+        let stem = texture
+            .rsplit('/')
+            .next()
+            .unwrap_or(texture)
+            .trim_end_matches(".png");
+        let mut name = stem.to_string();
+        let mut n = 2;
+        while self.data.kinds.contains_key(&name) {
+            name = format!("{}-{}", stem, n);
+            n += 1;
+        }
+        self.data.kinds.insert(
+            name.clone(),
+            TileKind {
+                frames: vec![Frame {
+                    texture: texture.to_string(),
+                    duration: 0.,
+                }],
+                walkable: true,
+            },
+        );
+        name
+    }
+
+    fn apply(&mut self, change: &Change, forward: bool) {
+        match change {
+            Change::Tile {
+                point,
+                before,
+                after,
+            } => {
+                let kind = if forward { after } else { before };
+                match kind {
+                    Some(kind) => self.tiles.insert(*point, kind.clone()),
+                    None => self.tiles.remove(point),
+                };
+                self.redraw.push(*point);
+            }
+            Change::Blocked {
+                point,
+                before,
+                after,
+            } => {
+                if *(if forward { after } else { before }) {
+                    self.blocked.insert(*point);
+                } else {
+                    self.blocked.remove(point);
+                }
+                self.markers_dirty = true;
+            }
+            Change::Portals { before, after } => {
+                let portals = if forward { after } else { before };
+                self.data
+                    .objects
+                    .retain(|object| !matches!(object.kind, ObjectKind::Portal { .. }));
+                self.data.objects.extend(portals.iter().cloned());
+                self.markers_dirty = true;
+            }
+            Change::Spawn { before, after } => {
+                self.data.spawn = if forward { *after } else { *before };
+                self.markers_dirty = true;
+            }
+        }
+        self.unsaved = true;
+    }
+
+    // Applies a change as part of the current stroke.
+    fn change(&mut self, change: Change) {
+        self.apply(&change, true);
+        self.stroke.push(change);
+    }
+
+    fn finish_stroke(&mut self) {
+        if self.stroke.is_empty() {
+            return;
+        }
+
+        let stroke = std::mem::take(&mut self.stroke);
+        self.undo.push(stroke);
+        self.redo.clear();
+    }
+
+    fn undo(&mut self) {
+        if let Some(edit) = self.undo.pop() {
+            for change in edit.iter().rev() {
+                self.apply(change, false);
+            }
+            self.redo.push(edit);
+        }
+    }
+
+    fn redo(&mut self) {
+        if let Some(edit) = self.redo.pop() {
+            for change in edit.iter() {
+                self.apply(change, true);
+            }
+            self.undo.push(edit);
+        }
+    }
+
+    fn paint_tile(&mut self, point: (i32, i32), kind: Option<String>) {
+        // Painting the fill is the same as no tile at all.
+        let kind = kind.filter(|kind| *kind != self.data.fill);
+        let before = self.tiles.get(&point).cloned();
+        if before == kind {
+            return;
+        }
+
+        self.change(Change::Tile {
+            point,
+            before,
+            after: kind,
+        });
+    }
+
+    fn paint_blocked(&mut self, point: (i32, i32), blocked: bool) {
+        let before = self.blocked.contains(&point);
+        if before == blocked {
+            return;
+        }
+
+        self.change(Change::Blocked {
+            point,
+            before,
+            after: blocked,
+        });
+    }
+
+    // Every tile connected to `start` that `same` says matches it, up to
+    // MAX_FILL of them.
+    fn flood(&self, start: (i32, i32), same: impl Fn((i32, i32)) -> bool) -> Vec<(i32, i32)> {
+        let mut seen = HashSet::new();
+        let mut queue = VecDeque::new();
+        let mut points = Vec::new();
+        seen.insert(start);
+        queue.push_back(start);
+
+        while let Some(point) = queue.pop_front() {
+            points.push(point);
+            if points.len() >= MAX_FILL {
+                break;
+            }
+
+            for (dx, dy) in [(1, 0), (-1, 0), (0, 1), (0, -1)].iter() {
+                let next = (point.0 + dx, point.1 + dy);
+                if self.in_bounds(next) && !seen.contains(&next) && same(next) {
+                    seen.insert(next);
+                    queue.push_back(next);
+                }
+            }
+        }
+        points
+    }
+
+    fn portals(&self) -> Vec<MapObject> {
+        self.data.portals().cloned().collect()
+    }
+
+    fn use_tool(&mut self, point: (i32, i32), just_pressed: bool) {
+        match (self.layer, self.tool) {
+            (Layer::Ground, Tool::Brush) => {
+                if let Some(texture) = self.selected.clone() {
+                    let kind = self.kind_for(&texture);
+                    self.paint_tile(point, Some(kind));
+                }
+            }
+            (Layer::Ground, Tool::Erase) => self.paint_tile(point, None),
+            (Layer::Ground, Tool::Fill) => {
+                let texture = match self.selected.clone() {
+                    Some(texture) => texture,
+                    None => return,
+                };
+                if !just_pressed {
+                    return;
+                }
+                let kind = self.kind_for(&texture);
+                let target = self.tiles.get(&point).cloned();
+                if *target.as_ref().unwrap_or(&self.data.fill) == kind {
+                    return;
+                }
+                for point in self.flood(point, |next| self.tiles.get(&next) == target.as_ref()) {
+                    self.paint_tile(point, Some(kind.clone()));
+                }
+            }
+            (Layer::Collision, Tool::Brush) => self.paint_blocked(point, true),
+            (Layer::Collision, Tool::Erase) => self.paint_blocked(point, false),
+            (Layer::Collision, Tool::Fill) => {
+                if !just_pressed {
+                    return;
+                }
+                let target = self.blocked.contains(&point);
+                for point in self.flood(point, |next| self.blocked.contains(&next) == target) {
+                    self.paint_blocked(point, !target);
+                }
+            }
+            (Layer::Portals, tool) => {
+                if !just_pressed {
+                    return;
+                }
+                let before = self.portals();
+                let mut after: Vec<MapObject> = before
+                    .iter()
+                    .filter(|portal| (portal.x, portal.y) != point)
+                    .cloned()
+                    .collect();
+                if tool != Tool::Erase {
+                    // New portals lead to the spawn point over there, move
+                    // it in the map file when it should go elsewhere.
+                    let (x, y) = MapData::load(&map::zone_path(self.portal_zone))
+                        .map(|data| data.spawn)
+                        .unwrap_or((0, 0));
+                    after.push(MapObject {
+                        x: point.0,
+                        y: point.1,
+                        kind: ObjectKind::Portal {
+                            zone: self.portal_zone,
+                            x,
+                            y,
+                        },
+                    });
+                }
+                if before.len() != after.len() || tool != Tool::Erase {
+                    self.change(Change::Portals { before, after });
+                }
+            }
+            (Layer::Spawn, tool) => {
+                if !just_pressed || tool == Tool::Erase || self.data.spawn == point {
+                    return;
+                }
+                self.change(Change::Spawn {
+                    before: self.data.spawn,
+                    after: point,
+                });
+            }
+        }
+    }
+
+    fn status(&self) -> String {
+        let selected = self
+            .selected
+            .as_deref()
+            .map_or("-", |texture| texture.rsplit('/').next().unwrap_or(texture));
+        format!(
+            "zone {}{} | {:?} on {:?} | tile {} | portals to zone {}\n\
+             B/F/E tools, 1-4 layers, [ ] portal zone, Ctrl+Z/Y undo/redo, \
+             Ctrl+S save, Ctrl+L reload\n{}",
+            self.zone_id,
+            if self.unsaved { "*" } else { "" },
+            self.tool,
+            self.layer,
+            selected,
+            self.portal_zone,
+            self.message,
+        )
+    }
+}
+
+pub struct EditorView;
+pub struct EditorMarker;
+pub struct EditorStatus;
+pub struct Palette;
+pub struct PaletteEntry {
+    texture: String,
+}
+
+pub fn setup(
+    mut commands: Commands,
+    mut editor: ResMut<Editor>,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    commands
+        .spawn()
+        .insert(Transform::default())
+        .insert(GlobalTransform::default())
+        .insert(EditorView)
+        .insert(CameraTarget);
+
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(PALETTE_TILE * PALETTE_COLUMNS as f32 + 20.),
+                    top: Val::Px(10.),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Medium.otf"),
+                    font_size: 18.0,
+                    color: Color::WHITE,
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(EditorStatus);
+
+    let colours = [
+        ("blocked", Color::rgba(1., 0.1, 0.1, 0.35)),
+        ("portal", Color::rgba(0.75, 0.35, 1., 0.6)),
+        ("spawn", Color::rgba(0.2, 1., 0.3, 0.6)),
+    ];
+    for (name, colour) in colours.iter() {
+        editor
+            .marker_materials
+            .insert(*name, materials.add((*colour).into()));
+    }
+}
+
+fn sprite_index(atlas: &TextureAtlas, asset_server: &AssetServer, texture: &str) -> usize {
+    let handle: Handle<Texture> = asset_server.get_handle(texture);
+    atlas.get_texture_index(&handle).unwrap()
+}
+
+fn tile_texture(editor: &Editor, point: (i32, i32)) -> String {
+    let kind = editor.tiles.get(&point).unwrap_or(&editor.data.fill);
+    editor.data.kinds[kind].frames[0].texture.clone()
+}
+
+// Brings the map up once the atlas is there, and again after a reload.
+pub fn load(
+    mut commands: Commands,
+    mut editor: ResMut<Editor>,
+    mut sprite_handles: ResMut<TileSpriteHandles>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    mut textures: ResMut<Assets<Texture>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    asset_server: Res<AssetServer>,
+) {
+    field::load_atlas(
+        &mut sprite_handles,
+        &mut texture_atlases,
+        &mut textures,
+        &asset_server,
+    );
+    if !sprite_handles.atlas_loaded || editor.loaded || editor.data.kinds.is_empty() {
+        return;
+    }
+
+    let mut bundle = field::tilemap_bundle(&editor.data, sprite_handles.atlas.clone());
+    let atlas = texture_atlases.get(&sprite_handles.atlas).unwrap();
+    let data = &editor.data;
+    let mut tiles = Vec::new();
+    for y in data.min_y()..data.min_y() + data.height {
+        for x in data.min_x()..data.min_x() + data.width {
+            tiles.push(Tile {
+                point: (x, y),
+                sprite_index: sprite_index(atlas, &asset_server, &tile_texture(&editor, (x, y))),
+                ..Default::default()
+            });
+        }
+    }
+    bundle.tilemap.insert_tiles(tiles).unwrap();
+    commands.spawn().insert_bundle(bundle);
+
+    // Every tile texture of the atlas, in a grid down the left edge.
+    let mut palette: Vec<String> = sprite_handles
+        .handles
+        .iter()
+        .filter_map(|handle| asset_server.get_handle_path(handle.id))
+        .filter_map(|path| path.path().to_str().map(|path| path.replace('\\', "/")))
+        .filter(|path| path.starts_with("texture/tiles/"))
+        .collect();
+    palette.sort();
+    let rows = (palette.len() + PALETTE_COLUMNS - 1) / PALETTE_COLUMNS;
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(0.),
+                    top: Val::Px(0.),
+                    ..Default::default()
+                },
+                size: Size::new(
+                    Val::Px(PALETTE_TILE * PALETTE_COLUMNS as f32),
+                    Val::Px(PALETTE_TILE * rows as f32),
+                ),
+                flex_wrap: FlexWrap::Wrap,
+                align_content: AlignContent::FlexStart,
+                ..Default::default()
+            },
+            material: materials.add(Color::rgb(0.1, 0.1, 0.1).into()),
+            ..Default::default()
+        })
+        .insert(Palette)
+        .with_children(|parent| {
+            for texture in palette.into_iter() {
+                let handle: Handle<Texture> = asset_server.get_handle(texture.as_str());
+                parent
+                    .spawn_bundle(ButtonBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(PALETTE_TILE), Val::Px(PALETTE_TILE)),
+                            ..Default::default()
+                        },
+                        material: materials.add(ColorMaterial {
+                            color: UNSELECTED,
+                            texture: Some(handle),
+                        }),
+                        ..Default::default()
+                    })
+                    .insert(PaletteEntry { texture });
+            }
+        });
+
+    editor.loaded = true;
+    editor.markers_dirty = true;
+}
+
+pub fn pick_from_palette(
+    mut editor: ResMut<Editor>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    clicked_query: Query<(&Interaction, &PaletteEntry), Changed<Interaction>>,
+    entry_query: Query<(&PaletteEntry, &Handle<ColorMaterial>)>,
+) {
+    for (interaction, entry) in clicked_query.iter() {
+        if *interaction == Interaction::Clicked {
+            editor.selected = Some(entry.texture.clone());
+            editor.layer = Layer::Ground;
+        }
+    }
+
+    for (entry, handle) in entry_query.iter() {
+        if let Some(material) = materials.get_mut(handle) {
+            material.color = if editor.selected.as_ref() == Some(&entry.texture) {
+                Color::WHITE
+            } else {
+                UNSELECTED
+            };
+        }
+    }
+}
+
+pub fn keyboard(
+    time: Res<Time>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut editor: ResMut<Editor>,
+    mut commands: Commands,
+    rebuild_query: Query<Entity, Or<(With<Tilemap>, With<Palette>)>>,
+    camera_query: Query<&OrthographicProjection, With<FieldCamera>>,
+    mut view_query: Query<&mut Transform, With<EditorView>>,
+) {
+    let ctrl =
+        keyboard_input.pressed(KeyCode::LControl) || keyboard_input.pressed(KeyCode::RControl);
+    let shift = keyboard_input.pressed(KeyCode::LShift) || keyboard_input.pressed(KeyCode::RShift);
+
+    if ctrl {
+        if keyboard_input.just_pressed(KeyCode::Z) {
+            if shift {
+                editor.redo();
+            } else {
+                editor.undo();
+            }
+        }
+        if keyboard_input.just_pressed(KeyCode::Y) {
+            editor.redo();
+        }
+        if keyboard_input.just_pressed(KeyCode::S) {
+            editor.write();
+        }
+        if keyboard_input.just_pressed(KeyCode::L) {
+            editor.read();
+            for entity in rebuild_query.iter() {
+                commands.entity(entity).despawn_recursive();
+            }
+            editor.loaded = false;
+        }
+        return;
+    }
+
+    let tools = [
+        (KeyCode::B, Tool::Brush),
+        (KeyCode::F, Tool::Fill),
+        (KeyCode::E, Tool::Erase),
+    ];
+    for (key, tool) in tools.iter() {
+        if keyboard_input.just_pressed(*key) {
+            editor.tool = *tool;
+        }
+    }
+    let layers = [
+        (KeyCode::Key1, Layer::Ground),
+        (KeyCode::Key2, Layer::Collision),
+        (KeyCode::Key3, Layer::Portals),
+        (KeyCode::Key4, Layer::Spawn),
+    ];
+    for (key, layer) in layers.iter() {
+        if keyboard_input.just_pressed(*key) {
+            editor.layer = *layer;
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::LBracket) {
+        editor.portal_zone = editor.portal_zone.saturating_sub(1);
+    }
+    if keyboard_input.just_pressed(KeyCode::RBracket) {
+        editor.portal_zone += 1;
+    }
+
+    let mut direction = Vec2::ZERO;
+    if keyboard_input.pressed(KeyCode::W) || keyboard_input.pressed(KeyCode::Up) {
+        direction.y += 1.;
+    }
+    if keyboard_input.pressed(KeyCode::S) || keyboard_input.pressed(KeyCode::Down) {
+        direction.y -= 1.;
+    }
+    if keyboard_input.pressed(KeyCode::A) || keyboard_input.pressed(KeyCode::Left) {
+        direction.x -= 1.;
+    }
+    if keyboard_input.pressed(KeyCode::D) || keyboard_input.pressed(KeyCode::Right) {
+        direction.x += 1.;
+    }
+    let scale = camera_query
+        .iter()
+        .next()
+        .map_or(1., |projection| projection.scale);
+    for mut transform in view_query.iter_mut() {
+        transform.translation += (direction * PAN_SPEED * scale * time.delta_seconds()).extend(0.);
+    }
+}
+
+pub fn paint(
+    windows: Res<Windows>,
+    mouse_input: Res<Input<MouseButton>>,
+    mut editor: ResMut<Editor>,
+    camera_query: Query<(&Transform, &OrthographicProjection), With<FieldCamera>>,
+    palette_query: Query<&Interaction, With<PaletteEntry>>,
+) {
+    if !editor.loaded {
+        return;
+    }
+    if mouse_input.just_released(MouseButton::Left) {
+        editor.finish_stroke();
+    }
+    if !mouse_input.pressed(MouseButton::Left) {
+        return;
+    }
+    // Clicks on the palette don't reach the map.
+    if palette_query
+        .iter()
+        .any(|interaction| *interaction != Interaction::None)
+    {
+        return;
+    }
+
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    let world = match camera_query
+        .iter()
+        .next()
+        .and_then(|(transform, projection)| cursor_to_world(window, transform, projection))
+    {
+        Some(world) => world,
+        None => return,
+    };
+    let tile = Position::from_world(world);
+    let point = (tile.x, tile.y);
+    if !editor.in_bounds(point) {
+        return;
+    }
+
+    editor.use_tool(point, mouse_input.just_pressed(MouseButton::Left));
+}
+
+fn spawn_marker(commands: &mut Commands, material: Handle<ColorMaterial>, point: (i32, i32)) {
+    let position = Position {
+        x: point.0,
+        y: point.1,
+    };
+    commands
+        .spawn_bundle(SpriteBundle {
+            material,
+            sprite: Sprite::new(Vec2::splat(TILE_SIZE)),
+            transform: Transform::from_translation(position.to_world().extend(MARKER_Z)),
+            ..Default::default()
+        })
+        .insert(EditorMarker);
+}
+
+// Puts the edits on screen.
+pub fn redraw(
+    mut commands: Commands,
+    mut editor: ResMut<Editor>,
+    texture_atlases: Res<Assets<TextureAtlas>>,
+    asset_server: Res<AssetServer>,
+    mut tilemap_query: Query<&mut Tilemap>,
+    marker_query: Query<Entity, With<EditorMarker>>,
+    mut status_query: Query<&mut Text, With<EditorStatus>>,
+) {
+    for mut text in status_query.iter_mut() {
+        text.sections[0].value = editor.status();
+    }
+    if !editor.loaded {
+        return;
+    }
+
+    let points = std::mem::take(&mut editor.redraw);
+    for mut map in tilemap_query.iter_mut() {
+        let atlas = texture_atlases.get(map.texture_atlas()).unwrap();
+        let tiles: Vec<Tile<(i32, i32)>> = points
+            .iter()
+            .map(|point| Tile {
+                point: *point,
+                sprite_index: sprite_index(atlas, &asset_server, &tile_texture(&editor, *point)),
+                ..Default::default()
+            })
+            .collect();
+        map.insert_tiles(tiles).unwrap();
+    }
+
+    if !editor.markers_dirty {
+        return;
+    }
+    for entity in marker_query.iter() {
+        commands.entity(entity).despawn();
+    }
+    let materials = &editor.marker_materials;
+    for point in editor.blocked.iter() {
+        spawn_marker(&mut commands, materials["blocked"].clone(), *point);
+    }
+    for portal in editor.data.portals() {
+        spawn_marker(
+            &mut commands,
+            materials["portal"].clone(),
+            (portal.x, portal.y),
+        );
+    }
+    spawn_marker(&mut commands, materials["spawn"].clone(), editor.data.spawn);
+    editor.markers_dirty = false;
+}
@@ -0,0 +1,338 @@
+use std::collections::HashMap;
+
+use super::animation::{AnimationState, Clip};
+use super::chat::Chat;
+use super::death::Respawn;
+use super::dialogue::Dialogue;
+use super::ButtonMaterials;
+use super::Character;
+use super::Player;
+use super::RequestSender;
+use super::Token;
+use crate::pursuit::api::mortalkin::{self, EmoteKind};
+
+use bevy::prelude::*;
+
+// Distance of the emote menu buttons from the middle of the window.
+const MENU_RADIUS: f32 = 110.;
+const BUTTON_WIDTH: f32 = 100.;
+const BUTTON_HEIGHT: f32 = 36.;
+
+pub struct EmoteDef {
+    kind: EmoteKind,
+    command: &'static str,
+    label: &'static str,
+    clip: Clip,
+    // Sitting stays down until the character walks away.
+    hold: bool,
+    // Seconds the clip plays over and over for.
+    repeat: f32,
+    cooldown: f64,
+    // Shown in the speaker's bubble, and in the chat as "Kin waves."
+    icon: &'static str,
+    action: &'static str,
+}
+
+const EMOTES: [EmoteDef; 4] = [
+    EmoteDef {
+        kind: EmoteKind::Wave,
+        command: "/wave",
+        label: "Wave",
+        clip: Clip::Jump,
+        hold: false,
+        repeat: 0.,
+        cooldown: 2.,
+        icon: "o/",
+        action: "waves",
+    },
+    EmoteDef {
+        kind: EmoteKind::Sit,
+        command: "/sit",
+        label: "Sit",
+        clip: Clip::Slide,
+        hold: true,
+        repeat: 0.,
+        cooldown: 1.,
+        icon: "...",
+        action: "sits down",
+    },
+    EmoteDef {
+        kind: EmoteKind::Dance,
+        command: "/dance",
+        label: "Dance",
+        clip: Clip::Jump,
+        hold: false,
+        repeat: 4.,
+        cooldown: 6.,
+        icon: "~\\o/~",
+        action: "dances",
+    },
+    EmoteDef {
+        kind: EmoteKind::Cheer,
+        command: "/cheer",
+        label: "Cheer",
+        clip: Clip::Jump,
+        hold: false,
+        repeat: 1.,
+        cooldown: 3.,
+        icon: "\\o/",
+        action: "cheers",
+    },
+];
+
+fn emote_def(kind: EmoteKind) -> &'static EmoteDef {
+    EMOTES.iter().find(|def| def.kind == kind).unwrap()
+}
+
+pub fn from_command(command: &str) -> Option<EmoteKind> {
+    EMOTES
+        .iter()
+        .find(|def| def.command == command)
+        .map(|def| def.kind)
+}
+
+// Sent by the chat and the emote menu when we want to do an emote.
+pub struct UseEmote(pub EmoteKind);
+
+#[derive(Default)]
+pub struct Emotes {
+    pub menu_open: bool,
+    // When each emote can be used again, in seconds since startup.
+    ready_at: HashMap<EmoteKind, f64>,
+}
+
+pub struct EmoteMenu;
+pub struct EmoteButton(EmoteKind);
+pub struct EmoteButtonText;
+
+pub fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    button_materials: Res<ButtonMaterials>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let hidden = Visible {
+        is_visible: false,
+        is_transparent: true,
+    };
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                ..Default::default()
+            },
+            material: materials.add(Color::rgba(0., 0., 0., 0.3).into()),
+            visible: hidden.clone(),
+            ..Default::default()
+        })
+        .insert(EmoteMenu)
+        .with_children(|parent| {
+            for (n, def) in EMOTES.iter().enumerate() {
+                parent
+                    .spawn_bundle(ButtonBundle {
+                        style: Style {
+                            position_type: PositionType::Absolute,
+                            size: Size::new(Val::Px(BUTTON_WIDTH), Val::Px(BUTTON_HEIGHT)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..Default::default()
+                        },
+                        material: button_materials.normal.clone(),
+                        visible: hidden.clone(),
+                        ..Default::default()
+                    })
+                    .insert(EmoteButton(def.kind))
+                    .with_children(|parent| {
+                        parent
+                            .spawn_bundle(TextBundle {
+                                text: Text::with_section(
+                                    format!("{} {}", n + 1, def.label),
+                                    TextStyle {
+                                        font: asset_server.load("fonts/FiraSans-Medium.otf"),
+                                        font_size: 18.,
+                                        color: Color::WHITE,
+                                    },
+                                    Default::default(),
+                                ),
+                                visible: hidden.clone(),
+                                ..Default::default()
+                            })
+                            .insert(EmoteButtonText);
+                    });
+            }
+        });
+}
+
+// G opens the menu around the middle of the window, picking an emote or G
+// again closes it.
+pub fn toggle_menu(
+    windows: Res<Windows>,
+    keyboard_input: Res<Input<KeyCode>>,
+    chat: Res<Chat>,
+    dialogue: Res<Dialogue>,
+    respawn: Res<Respawn>,
+    mut emotes: ResMut<Emotes>,
+    mut button_query: Query<(&EmoteButton, &mut Style)>,
+    mut visible_query: Query<
+        &mut Visible,
+        Or<(With<EmoteMenu>, With<EmoteButton>, With<EmoteButtonText>)>,
+    >,
+) {
+    if chat.typing {
+        return;
+    }
+    // It doesn't open over a dialogue or while dead, an open one can still be
+    // closed.
+    let blocked = dialogue.is_open() || respawn.is_dead();
+    let toggle = keyboard_input.just_pressed(KeyCode::G) && (emotes.menu_open || !blocked);
+    let close = emotes.menu_open && keyboard_input.just_pressed(KeyCode::Escape);
+    if !toggle && !close {
+        return;
+    }
+
+    emotes.menu_open = toggle && !emotes.menu_open;
+    for mut visible in visible_query.iter_mut() {
+        visible.is_visible = emotes.menu_open;
+    }
+    if !emotes.menu_open {
+        return;
+    }
+
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    let middle = Vec2::new(window.width(), window.height()) / 2.;
+    let count = EMOTES.len() as f32;
+    for (button, mut style) in button_query.iter_mut() {
+        // The first one at the top, going clockwise.
+        let n = EMOTES.iter().position(|def| def.kind == button.0).unwrap();
+        let angle = std::f32::consts::FRAC_PI_2 - n as f32 / count * std::f32::consts::TAU;
+        let corner = middle + Vec2::new(angle.cos(), angle.sin()) * MENU_RADIUS
+            - Vec2::new(BUTTON_WIDTH, BUTTON_HEIGHT) / 2.;
+        style.position = Rect {
+            left: Val::Px(corner.x),
+            bottom: Val::Px(corner.y),
+            ..Default::default()
+        };
+    }
+}
+
+pub fn menu_input(
+    keyboard_input: Res<Input<KeyCode>>,
+    button_materials: Res<ButtonMaterials>,
+    mut emotes: ResMut<Emotes>,
+    mut emote_events: EventWriter<UseEmote>,
+    mut button_query: Query<
+        (&Interaction, &EmoteButton, &mut Handle<ColorMaterial>),
+        Changed<Interaction>,
+    >,
+    mut visible_query: Query<
+        &mut Visible,
+        Or<(With<EmoteMenu>, With<EmoteButton>, With<EmoteButtonText>)>,
+    >,
+) {
+    // The buttons still notice the mouse while hidden.
+    if !emotes.menu_open {
+        return;
+    }
+
+    let mut picked = None;
+    for (interaction, button, mut material) in button_query.iter_mut() {
+        *material = match *interaction {
+            Interaction::Clicked => {
+                picked = Some(button.0);
+                button_materials.pressed.clone()
+            }
+            Interaction::Hovered => button_materials.hovered.clone(),
+            Interaction::None => button_materials.normal.clone(),
+        };
+    }
+    let keys = [KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4];
+    for (key, def) in keys.iter().zip(EMOTES.iter()) {
+        if keyboard_input.just_pressed(*key) {
+            picked = Some(def.kind);
+        }
+    }
+
+    if let Some(kind) = picked {
+        emote_events.send(UseEmote(kind));
+        emotes.menu_open = false;
+        for mut visible in visible_query.iter_mut() {
+            visible.is_visible = false;
+        }
+    }
+}
+
+// Sends our emotes to the server unless they're cooling down. They play when
+// the server sends them back, like everyone else's.
+pub fn use_emotes(
+    time: Res<Time>,
+    mut events: EventReader<UseEmote>,
+    mut emotes: ResMut<Emotes>,
+    mut chat: ResMut<Chat>,
+    request_sender: Res<RequestSender>,
+    token: Res<Token>,
+    current_char: Res<Character>,
+) {
+    let now = time.seconds_since_startup();
+    for UseEmote(kind) in events.iter() {
+        let def = emote_def(*kind);
+        let ready_at = *emotes.ready_at.get(kind).unwrap_or(&0.);
+        if now < ready_at {
+            chat.system(format!(
+                "{} is ready again in {:.0}s.",
+                def.label,
+                (ready_at - now).ceil()
+            ));
+            continue;
+        }
+        emotes.ready_at.insert(*kind, now + def.cooldown);
+
+        request_sender
+            .tx
+            .lock()
+            .unwrap()
+            .unbounded_send(mortalkin::PlayGamePayload {
+                token: token.token.clone(),
+                character_id: current_char.id,
+                position: None,
+                enter_portal: None,
+                chat: None,
+                emote: Some(mortalkin::Emote {
+                    character_id: current_char.id,
+                    kind: *kind as i32,
+                }),
+                interact: None,
+                attack: None,
+            })
+            .unwrap();
+    }
+}
+
+// Emotes from the server, ours included.
+pub fn play(
+    mut events: EventReader<mortalkin::Emote>,
+    mut chat: ResMut<Chat>,
+    mut query: Query<(&Player, &mut AnimationState)>,
+) {
+    for emote in events.iter() {
+        let kind = EmoteKind::from_i32(emote.kind).unwrap_or(EmoteKind::Wave);
+        let def = emote_def(kind);
+        for (player, mut state) in query.iter_mut() {
+            if player.id != emote.character_id {
+                continue;
+            }
+
+            if def.hold {
+                state.hold(def.clip);
+            } else if def.repeat > 0. {
+                state.repeat(def.clip, def.repeat);
+            } else {
+                state.play(def.clip);
+            }
+            chat.action(player.id, &player.name, def.action, def.icon);
+        }
+    }
+}
@@ -1,10 +1,23 @@
+use std::collections::{HashMap, HashSet};
+
+use super::animation::CharacterAnimations;
+use super::camera::CameraTarget;
+use super::chat::Chat;
+use super::collision::CollisionMap;
+use super::interaction;
+use super::map::{MapData, TileKind};
+use super::map_cache::{self, CacheIndex, MapRequestSender, MapResponseReceiver};
+use super::movement::MoveIntent;
+use super::npc;
+use super::props;
+use super::terrain::{self, Tileset};
 use super::Character;
 use super::GameMap;
-use super::Player;
 use super::PlayerBundle;
 use super::Position;
-use super::Render;
 use super::TileSpriteHandles;
+use super::Token;
+use crate::pursuit::api::mortalkin::GetMapPayload;
 
 use bevy::{asset::LoadState, prelude::*, sprite::TextureAtlasBuilder};
 
@@ -12,15 +25,56 @@ use bevy_tilemap::prelude::*;
 
 const CHUNK_WIDTH: u32 = 64;
 const CHUNK_HEIGHT: u32 = 64;
-const TILEMAP_WIDTH: i32 = CHUNK_WIDTH as i32 * 40;
-const TILEMAP_HEIGHT: i32 = CHUNK_HEIGHT as i32 * 40;
 
-pub fn load(
-    mut commands: Commands,
-    mut sprite_handles: ResMut<TileSpriteHandles>,
-    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
-    mut textures: ResMut<Assets<Texture>>,
-    asset_server: Res<AssetServer>,
+#[derive(Default)]
+pub struct AnimatedTiles {
+    animations: Vec<TileAnimation>,
+}
+
+// Every tile of one animated kind shares a single animation, so neighbouring
+// water tiles always show the same frame.
+struct TileAnimation {
+    // Atlas index of each frame and how long it stays on screen.
+    frames: Vec<(usize, f64)>,
+    cycle: f64,
+    current: usize,
+    points: Vec<(i32, i32)>,
+}
+
+impl TileAnimation {
+    fn new(kind: &TileKind, indices: &[usize]) -> Self {
+        let frames: Vec<(usize, f64)> = indices
+            .iter()
+            .zip(kind.frames.iter())
+            .map(|(index, frame)| (*index, frame.duration as f64))
+            .collect();
+        Self {
+            cycle: frames.iter().map(|(_, duration)| duration).sum(),
+            frames,
+            current: 0,
+            points: Vec::new(),
+        }
+    }
+
+    fn frame_at(&self, clock: f64) -> usize {
+        let mut time = clock % self.cycle;
+        for (n, (_, duration)) in self.frames.iter().enumerate() {
+            if time < *duration {
+                return n;
+            }
+            time -= duration;
+        }
+
+        self.frames.len() - 1
+    }
+}
+
+// Builds the atlas out of every texture once they're all loaded.
+pub fn load_atlas(
+    sprite_handles: &mut TileSpriteHandles,
+    texture_atlases: &mut Assets<TextureAtlas>,
+    textures: &mut Assets<Texture>,
+    asset_server: &AssetServer,
 ) {
     if sprite_handles.atlas_loaded {
         return;
@@ -36,118 +90,336 @@ pub fn load(
             texture_atlas_builder.add_texture(handle.clone_weak().typed::<Texture>(), &texture);
         }
 
-        let texture_atlas = texture_atlas_builder.finish(&mut textures).unwrap();
-        let atlas_handle = texture_atlases.add(texture_atlas);
-
-        // These are fairly advanced configurations just to quickly showcase
-        // them.
-        let tilemap = Tilemap::builder()
-            .dimensions(TILEMAP_WIDTH as u32, TILEMAP_HEIGHT as u32)
-            .chunk_dimensions(CHUNK_WIDTH, CHUNK_HEIGHT, 1)
-            .texture_dimensions(32, 32)
-            .auto_chunk()
-            .auto_spawn(2, 2)
-            .add_layer(
-                TilemapLayer {
-                    kind: LayerKind::Dense,
-                    ..Default::default()
-                },
-                0,
-            )
-            .texture_atlas(atlas_handle)
-            .finish()
-            .unwrap();
+        let texture_atlas = texture_atlas_builder.finish(textures).unwrap();
+        sprite_handles.atlas = texture_atlases.add(texture_atlas);
+        sprite_handles.atlas_loaded = true;
+    }
+}
+
+// Atlas index of every frame of a tile kind. Downloaded maps can name kinds
+// or textures this client doesn't have.
+fn kind_frames<'a>(
+    data: &'a MapData,
+    name: &str,
+    texture_atlas: &TextureAtlas,
+    asset_server: &AssetServer,
+) -> Result<(&'a TileKind, Vec<usize>), String> {
+    let kind = data
+        .kinds
+        .get(name)
+        .ok_or_else(|| format!("unknown tile kind {}", name))?;
+    if kind.frames.is_empty() {
+        return Err(format!("tile kind {} has no frames", name));
+    }
+    // Without a duration on every frame there'd be no cycle to loop through.
+    if kind.is_animated() && kind.frames.iter().any(|frame| frame.duration <= 0.) {
+        return Err(format!("tile kind {} has a frame without a duration", name));
+    }
+
+    let indices = kind
+        .frames
+        .iter()
+        .map(|frame| {
+            let handle: Handle<Texture> = asset_server.get_handle(frame.texture.as_str());
+            texture_atlas
+                .get_texture_index(&handle)
+                .ok_or_else(|| format!("no texture {} for tile kind {}", frame.texture, name))
+        })
+        .collect::<Result<Vec<usize>, String>>()?;
+    Ok((kind, indices))
+}
+
+// Reads a zone, generating its terrain, and checks every tile on it can be
+// drawn before anything gets built.
+fn read_map(
+    path: &str,
+    texture_atlas: &TextureAtlas,
+    asset_server: &AssetServer,
+) -> Result<MapData, Box<dyn std::error::Error>> {
+    let mut data = MapData::load(path)?;
+    if data.generator.is_some() {
+        let tileset = Tileset::load(terrain::TERRAIN_PATH)?;
+        terrain::apply(&mut data, &tileset, CHUNK_WIDTH as i32, CHUNK_HEIGHT as i32);
+    }
+
+    let mut names: HashSet<&str> = data
+        .tiles
+        .iter()
+        .map(|placed| placed.kind.as_str())
+        .collect();
+    names.insert(&data.fill);
+    for name in names {
+        kind_frames(&data, name, texture_atlas, asset_server)?;
+    }
+    Ok(data)
+}
 
-        let tilemap_components = TilemapBundle {
-            tilemap,
-            visible: Visible {
-                is_visible: true,
-                is_transparent: true,
+pub fn tilemap_bundle(data: &MapData, atlas: Handle<TextureAtlas>) -> TilemapBundle {
+    // These are fairly advanced configurations just to quickly showcase
+    // them.
+    let tilemap = Tilemap::builder()
+        .dimensions(data.width as u32, data.height as u32)
+        .chunk_dimensions(CHUNK_WIDTH, CHUNK_HEIGHT, 1)
+        .texture_dimensions(32, 32)
+        .auto_chunk()
+        .auto_spawn(2, 2)
+        .add_layer(
+            TilemapLayer {
+                kind: LayerKind::Dense,
+                ..Default::default()
             },
-            transform: Default::default(),
-            global_transform: Default::default(),
-        };
-        commands
-            .spawn()
-            .insert_bundle(OrthographicCameraBundle::new_2d());
-        commands
-            .spawn()
-            .insert_bundle(tilemap_components)
-            .insert(Timer::from_seconds(0.075, true));
+            0,
+        )
+        .texture_atlas(atlas)
+        .finish()
+        .unwrap();
 
-        sprite_handles.atlas_loaded = true;
+    TilemapBundle {
+        tilemap,
+        visible: Visible {
+            is_visible: true,
+            is_transparent: true,
+        },
+        transform: Default::default(),
+        global_transform: Default::default(),
+    }
+}
+
+pub fn load(
+    mut commands: Commands,
+    mut game_state: ResMut<GameMap>,
+    mut sprite_handles: ResMut<TileSpriteHandles>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    mut textures: ResMut<Assets<Texture>>,
+    asset_server: Res<AssetServer>,
+    current_char: Res<Character>,
+    token: Res<Token>,
+    mut cache_index: ResMut<CacheIndex>,
+    map_request_sender: Res<MapRequestSender>,
+    map_response_receiver: Res<MapResponseReceiver>,
+    mut chat: ResMut<Chat>,
+) {
+    load_atlas(
+        &mut sprite_handles,
+        &mut texture_atlases,
+        &mut textures,
+        &asset_server,
+    );
+
+    // The atlas is built once, the tilemap again for every zone we enter.
+    if game_state.tilemap_spawned {
+        return;
+    }
+
+    // Ask the server whether our copy of the map is current while the
+    // textures load.
+    if !game_state.map_requested {
+        map_request_sender
+            .tx
+            .lock()
+            .unwrap()
+            .send(GetMapPayload {
+                token: token.token.clone(),
+                zone_id: current_char.zone_id,
+                version: cache_index.version(current_char.zone_id),
+            })
+            .unwrap();
+        game_state.map_requested = true;
     }
+    if !sprite_handles.atlas_loaded {
+        return;
+    }
+
+    let response = match map_response_receiver.rx.lock().unwrap().try_recv() {
+        Ok(response) => response,
+        Err(_) => return,
+    };
+    // A map that doesn't load isn't kept around, the one shipped with the
+    // client is used instead.
+    let texture_atlas = texture_atlases.get(&sprite_handles.atlas).unwrap();
+    let path = map_cache::resolve(&mut cache_index, current_char.zone_id, response);
+    game_state.data = match read_map(&path, texture_atlas, &asset_server) {
+        Ok(data) => data,
+        Err(err) => {
+            warn!("Can't load map {}: {}", path, err);
+            if let Err(err) = cache_index.forget(current_char.zone_id) {
+                warn!("Can't drop map {} from the cache: {}", path, err);
+            }
+            let path = map_cache::fallback(&cache_index, current_char.zone_id);
+            match read_map(&path, texture_atlas, &asset_server) {
+                Ok(data) => data,
+                Err(err) => {
+                    // Nothing left to try, the zone stays empty and the
+                    // request isn't sent again.
+                    warn!("Can't load map {}: {}", path, err);
+                    chat.system(format!("Can't load this zone's map: {}", err));
+                    return;
+                }
+            }
+        }
+    };
+
+    commands.spawn().insert_bundle(tilemap_bundle(
+        &game_state.data,
+        sprite_handles.atlas.clone(),
+    ));
+
+    game_state.tilemap_spawned = true;
 }
 
 pub fn build(
     mut commands: Commands,
     mut game_state: ResMut<GameMap>,
+    mut animated_tiles: ResMut<AnimatedTiles>,
     texture_atlases: Res<Assets<TextureAtlas>>,
     asset_server: Res<AssetServer>,
     mut query: Query<&mut Tilemap>,
     current_char: Res<Character>,
+    animations: Res<CharacterAnimations>,
 ) {
-    if game_state.map_loaded {
+    if game_state.map_loaded || !animations.loaded {
         return;
     }
 
     for mut map in query.iter_mut() {
-        let floor_sprite: Handle<Texture> =
-            asset_server.get_handle("texture/tiles/generic-rpg-Slice.png");
-        let wall_sprite: Handle<Texture> =
-            asset_server.get_handle("texture/tiles/generic-rpg-tile02.png");
         let texture_atlas = texture_atlases.get(map.texture_atlas()).unwrap();
-        let floor_idx = texture_atlas.get_texture_index(&floor_sprite).unwrap();
-        let wall_idx = texture_atlas.get_texture_index(&wall_sprite).unwrap();
+        // Characters coming through a portal appear where it leads, everyone
+        // else at the map's spawn point.
+        let spawn = game_state.data.spawn;
+        let arrival = game_state.arrival.take().unwrap_or(Position {
+            x: spawn.0,
+            y: spawn.1,
+        });
 
-        // Now we fill the entire space with floors.
+        let data = &game_state.data;
+        let mut collision = CollisionMap::new(data.min_x(), data.min_y(), data.width, data.height);
+
+        // Now we fill the entire space with the fill kind. It's never
+        // animated, that would put every tile of the map in one animation.
+        // `load` checked the kinds, anything missing here is left out.
         let mut tiles = Vec::new();
-        for y in 0..TILEMAP_HEIGHT {
-            for x in 0..TILEMAP_WIDTH {
-                let y = y - TILEMAP_HEIGHT / 2;
-                let x = x - TILEMAP_WIDTH / 2;
-                // By default tile sets the Z order at 0. Lower means that tile
-                // will render lower than others. 0 is the absolute bottom
-                // level which is perfect for backgrounds.
-                let tile = Tile {
-                    point: (x, y),
-                    sprite_index: floor_idx,
-                    ..Default::default()
+        match kind_frames(data, &data.fill, texture_atlas, &asset_server) {
+            Ok((fill, indices)) => {
+                for y in data.min_y()..data.min_y() + data.height {
+                    for x in data.min_x()..data.min_x() + data.width {
+                        // By default tile sets the Z order at 0. Lower means
+                        // that tile will render lower than others. 0 is the
+                        // absolute bottom level which is perfect for
+                        // backgrounds.
+                        let tile = Tile {
+                            point: (x, y),
+                            sprite_index: indices[0],
+                            ..Default::default()
+                        };
+                        tiles.push(tile);
+                        if !fill.walkable {
+                            collision.set_walkable(x, y, false);
+                        }
+                    }
+                }
+            }
+            Err(err) => warn!("Can't fill the map: {}", err),
+        }
+
+        // Placed tiles replace the fill underneath.
+        let mut tile_animations: HashMap<&str, TileAnimation> = HashMap::new();
+        for placed in data.tiles.iter() {
+            let (kind, indices) =
+                match kind_frames(data, &placed.kind, texture_atlas, &asset_server) {
+                    Ok(found) => found,
+                    Err(err) => {
+                        warn!("Skipping tile at {}, {}: {}", placed.x, placed.y, err);
+                        continue;
+                    }
                 };
-                tiles.push(tile);
+            tiles.push(Tile {
+                point: (placed.x, placed.y),
+                sprite_index: indices[0],
+                ..Default::default()
+            });
+            collision.set_walkable(placed.x, placed.y, kind.walkable);
+
+            if kind.is_animated() {
+                tile_animations
+                    .entry(placed.kind.as_str())
+                    .or_insert_with(|| TileAnimation::new(kind, &indices))
+                    .points
+                    .push((placed.x, placed.y));
             }
         }
+        for (x, y) in data.blocked.iter() {
+            collision.set_walkable(*x, *y, false);
+        }
+        animated_tiles.animations = tile_animations
+            .into_iter()
+            .map(|(_, animation)| animation)
+            .collect();
 
-        tiles.push(Tile {
-            point: (0, 0),
-            sprite_index: wall_idx,
-            ..Default::default()
-        });
+        props::spawn_props(
+            &mut commands,
+            data,
+            &mut collision,
+            map.texture_atlas(),
+            texture_atlas,
+            &asset_server,
+        );
+        npc::spawn_npcs(
+            &mut commands,
+            data,
+            map.texture_atlas(),
+            texture_atlas,
+            &asset_server,
+        );
+        interaction::spawn_portals(&mut commands, data);
 
-        let dwarf_sprite: Handle<Texture> = asset_server.get_handle("texture/sprite/sensei.png");
-        let dwarf_sprite_index = texture_atlas.get_texture_index(&dwarf_sprite).unwrap();
-        // We add in a Z order of 1 to place the tile above the background on Z
-        // order 0.
-        let dwarf_tile = Tile {
-            point: (2, 2),
-            sprite_order: 1,
-            sprite_index: dwarf_sprite_index,
-            ..Default::default()
-        };
-        tiles.push(dwarf_tile);
-
-        commands.spawn().insert_bundle(PlayerBundle {
-            player: Player {
-                id: current_char.id,
-            },
-            position: Position { x: 2, y: 2 },
-            render: Render {
-                sprite_index: dwarf_sprite_index,
-                sprite_order: 1,
-            },
-        });
+        commands
+            .spawn()
+            .insert_bundle(PlayerBundle::new(
+                current_char.id,
+                current_char.name.clone(),
+                arrival,
+                &animations,
+            ))
+            .insert(MoveIntent::default())
+            .insert(CameraTarget);
 
         map.insert_tiles(tiles).unwrap();
+        game_state.collision = collision;
         game_state.map_loaded = true;
     }
 }
+
+pub fn animate_tiles(
+    time: Res<Time>,
+    mut animated_tiles: ResMut<AnimatedTiles>,
+    mut query: Query<&mut Tilemap>,
+) {
+    // Frames come from the time since startup rather than a per-tile timer,
+    // which keeps every animation in lockstep.
+    let clock = time.seconds_since_startup();
+    for mut map in query.iter_mut() {
+        for animation in animated_tiles.animations.iter_mut() {
+            let frame = animation.frame_at(clock);
+            if frame == animation.current {
+                continue;
+            }
+
+            // Only the tiles of an animation whose frame just changed are
+            // touched, the rest of the chunk stays as it is.
+            animation.current = frame;
+            let sprite_index = animation.frames[frame].0;
+            let tiles: Vec<Tile<(i32, i32)>> = animation
+                .points
+                .iter()
+                .map(|point| Tile {
+                    point: *point,
+                    sprite_index,
+                    ..Default::default()
+                })
+                .collect();
+            if let Err(err) = map.insert_tiles(tiles) {
+                warn!("Can't animate tiles: {}", err);
+            }
+        }
+    }
+}
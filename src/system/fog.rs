@@ -0,0 +1,299 @@
+use std::fs;
+
+use super::collision::CollisionMap;
+use super::Character;
+use super::GameMap;
+use super::Player;
+use super::Position;
+use super::TILE_SIZE;
+
+use bevy::{
+    prelude::*,
+    render::texture::{Extent3d, FilterMode, SamplerDescriptor, TextureDimension, TextureFormat},
+};
+
+// Tiles the local player sees in every direction.
+const VISION_RADIUS: i32 = 8;
+const EXPLORED_DIR: &str = "cache/explored";
+const SAVE_SECONDS: f32 = 5.;
+// Tiles across the overlay, centred on the local player. Maps are far too
+// big for a pixel per tile of the whole thing, this covers the view at the
+// furthest zoom.
+const OVERLAY_TILES: i32 = 129;
+// Above the characters and props, below nothing but the UI.
+const FOG_Z: f32 = 200.;
+
+// Darkness of a tile on the overlay.
+const UNEXPLORED: u8 = 255;
+const EXPLORED: u8 = 150;
+const VISIBLE: u8 = 0;
+
+// Which tiles of the current zone the character has ever seen, and the
+// darkness overlay drawn from it.
+pub struct Fog {
+    // Zone the explored tiles belong to, `None` until its map is built.
+    zone_id: Option<u32>,
+    character_id: u32,
+    min_x: i32,
+    min_y: i32,
+    width: i32,
+    height: i32,
+    explored: Vec<bool>,
+    center: Option<Position>,
+    texture: Handle<Texture>,
+    dirty: bool,
+    save_timer: Timer,
+}
+
+impl Default for Fog {
+    fn default() -> Self {
+        Self {
+            zone_id: None,
+            character_id: 0,
+            min_x: 0,
+            min_y: 0,
+            width: 0,
+            height: 0,
+            explored: Vec::new(),
+            center: None,
+            texture: Default::default(),
+            dirty: false,
+            save_timer: Timer::from_seconds(SAVE_SECONDS, true),
+        }
+    }
+}
+
+impl Fog {
+    fn path(character_id: u32, zone_id: u32) -> String {
+        format!("{}/{}/zone{}.bin", EXPLORED_DIR, character_id, zone_id)
+    }
+
+    // Explored tiles are stored as a bitset, one bit per tile in row order.
+    // A file that doesn't fit the map, e.g. after the map was resized, is
+    // thrown away.
+    fn load(&mut self) {
+        let tiles = (self.width * self.height) as usize;
+        self.explored = vec![false; tiles];
+        let bytes = match fs::read(Self::path(self.character_id, self.zone_id.unwrap())) {
+            Ok(bytes) => bytes,
+            Err(_) => return,
+        };
+        if bytes.len() != (tiles + 7) / 8 {
+            return;
+        }
+
+        for (index, explored) in self.explored.iter_mut().enumerate() {
+            *explored = bytes[index / 8] & (1 << (index % 8)) != 0;
+        }
+    }
+
+    fn save(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let zone_id = match self.zone_id {
+            Some(zone_id) => zone_id,
+            None => return Ok(()),
+        };
+
+        let mut bytes = vec![0u8; (self.explored.len() + 7) / 8];
+        for (index, explored) in self.explored.iter().enumerate() {
+            if *explored {
+                bytes[index / 8] |= 1 << (index % 8);
+            }
+        }
+        fs::create_dir_all(format!("{}/{}", EXPLORED_DIR, self.character_id))?;
+        fs::write(Self::path(self.character_id, zone_id), bytes)?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        if x < self.min_x
+            || y < self.min_y
+            || x >= self.min_x + self.width
+            || y >= self.min_y + self.height
+        {
+            return None;
+        }
+
+        Some(((y - self.min_y) * self.width + (x - self.min_x)) as usize)
+    }
+}
+
+pub struct FogOverlay;
+
+// Walks the tiles between two points and fails on the first wall. The end
+// points themselves don't count, so walls are seen but not seen through.
+fn line_of_sight(collision: &CollisionMap, from: Position, to: Position) -> bool {
+    let dx = (to.x - from.x).abs();
+    let dy = -(to.y - from.y).abs();
+    let step_x = (to.x - from.x).signum();
+    let step_y = (to.y - from.y).signum();
+    let mut error = dx + dy;
+    let (mut x, mut y) = (from.x, from.y);
+
+    loop {
+        let doubled = error * 2;
+        if doubled >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if doubled <= dx {
+            error += dx;
+            y += step_y;
+        }
+        if x == to.x && y == to.y {
+            return true;
+        }
+        if collision.is_wall(x, y) {
+            return false;
+        }
+    }
+}
+
+fn spawn_overlay(
+    commands: &mut Commands,
+    fog: &mut Fog,
+    textures: &mut Assets<Texture>,
+    materials: &mut Assets<ColorMaterial>,
+) {
+    let mut texture = Texture::new_fill(
+        Extent3d::new(OVERLAY_TILES as u32, OVERLAY_TILES as u32, 1),
+        TextureDimension::D2,
+        &[0, 0, 0, UNEXPLORED],
+        TextureFormat::Rgba8UnormSrgb,
+    );
+    // One pixel per tile, blended between pixels so the edge of the vision
+    // fades out instead of showing every tile.
+    texture.sampler = SamplerDescriptor {
+        mag_filter: FilterMode::Linear,
+        ..Default::default()
+    };
+    fog.texture = textures.add(texture);
+
+    commands
+        .spawn_bundle(SpriteBundle {
+            material: materials.add(fog.texture.clone().into()),
+            transform: Transform {
+                translation: Vec3::new(0., 0., FOG_Z),
+                scale: Vec3::new(TILE_SIZE, TILE_SIZE, 1.),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(FogOverlay);
+}
+
+pub fn reveal(
+    mut commands: Commands,
+    mut fog: ResMut<Fog>,
+    mut textures: ResMut<Assets<Texture>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    game_state: Res<GameMap>,
+    current_char: Res<Character>,
+    player_query: Query<(&Player, &Position)>,
+    mut overlay_query: Query<(Entity, &mut Transform), With<FogOverlay>>,
+) {
+    if !game_state.map_loaded {
+        // The zone is being left, keep what was explored and start over once
+        // the next map is built.
+        if fog.zone_id.is_some() {
+            if let Err(err) = fog.save() {
+                warn!("Can't save explored tiles: {}", err);
+            }
+            for (entity, _) in overlay_query.iter_mut() {
+                commands.entity(entity).despawn();
+            }
+            *fog = Fog::default();
+        }
+        return;
+    }
+
+    if fog.zone_id.is_none() {
+        let data = &game_state.data;
+        fog.zone_id = Some(current_char.zone_id);
+        fog.character_id = current_char.id;
+        fog.min_x = data.min_x();
+        fog.min_y = data.min_y();
+        fog.width = data.width;
+        fog.height = data.height;
+        fog.load();
+        spawn_overlay(&mut commands, &mut fog, &mut textures, &mut materials);
+        // The overlay only exists once the commands are applied.
+        return;
+    }
+
+    let center = match player_query
+        .iter()
+        .find(|(player, _)| player.id == current_char.id)
+    {
+        Some((_, position)) => *position,
+        None => return,
+    };
+    if fog.center == Some(center) {
+        return;
+    }
+    fog.center = Some(center);
+
+    let mut visible = Vec::new();
+    for dy in -VISION_RADIUS..=VISION_RADIUS {
+        for dx in -VISION_RADIUS..=VISION_RADIUS {
+            if dx * dx + dy * dy > VISION_RADIUS * VISION_RADIUS {
+                continue;
+            }
+            let tile = Position {
+                x: center.x + dx,
+                y: center.y + dy,
+            };
+            let index = match fog.index(tile.x, tile.y) {
+                Some(index) => index,
+                None => continue,
+            };
+            if tile == center || line_of_sight(&game_state.collision, center, tile) {
+                visible.push((tile, index));
+            }
+        }
+    }
+    for (_, index) in visible.iter() {
+        if !fog.explored[*index] {
+            fog.explored[*index] = true;
+            fog.dirty = true;
+        }
+    }
+
+    let texture = match textures.get_mut(&fog.texture) {
+        Some(texture) => texture,
+        None => return,
+    };
+    // The top row of the texture is north, the middle pixel our tile.
+    let half = OVERLAY_TILES / 2;
+    let alpha_offset = |x: i32, y: i32| {
+        let column = x - center.x + half;
+        let row = center.y + half - y;
+        ((row * OVERLAY_TILES + column) * 4 + 3) as usize
+    };
+    for y in center.y - half..=center.y + half {
+        for x in center.x - half..=center.x + half {
+            let explored = fog.index(x, y).map_or(false, |index| fog.explored[index]);
+            texture.data[alpha_offset(x, y)] = if explored { EXPLORED } else { UNEXPLORED };
+        }
+    }
+    for (tile, _) in visible.iter() {
+        texture.data[alpha_offset(tile.x, tile.y)] = VISIBLE;
+    }
+
+    for (_, mut transform) in overlay_query.iter_mut() {
+        let point = center.to_world();
+        transform.translation.x = point.x;
+        transform.translation.y = point.y;
+    }
+}
+
+pub fn save_explored(time: Res<Time>, mut fog: ResMut<Fog>) {
+    if !fog.save_timer.tick(time.delta()).just_finished() || !fog.dirty {
+        return;
+    }
+
+    // Stays dirty when it fails, so it's tried again on the next tick.
+    if let Err(err) = fog.save() {
+        warn!("Can't save explored tiles: {}", err);
+    }
+}
@@ -0,0 +1,350 @@
+use super::chat::Chat;
+use super::death::Respawn;
+use super::dialogue::{Dialogue, DialogueTree};
+use super::map::{InteractionKind, MapData};
+use super::npc::Npc;
+use super::props::Prop;
+use super::Character;
+use super::GameMap;
+use super::Player;
+use super::Position;
+use super::RequestSender;
+use super::Token;
+use crate::pursuit::api::mortalkin;
+
+use bevy::prelude::*;
+
+// Above the dialogue box, so the prompt doesn't jump when one opens.
+const PROMPT_BOTTOM: f32 = 230.;
+const FONT_SIZE: f32 = 20.;
+// Seconds we wait for the server before E does something again.
+const PENDING_SECONDS: f64 = 2.;
+
+// Anything E does something to, standing next to its `Position`. NPCs, props
+// and portals all get one when the map is built.
+pub struct Interactable {
+    pub kind: InteractionKind,
+    pub name: String,
+}
+
+// What a sign says, read once the server agrees.
+pub struct Sign {
+    pub text: String,
+}
+
+fn to_proto(kind: InteractionKind) -> mortalkin::InteractionKind {
+    match kind {
+        InteractionKind::Talk => mortalkin::InteractionKind::Talk,
+        InteractionKind::Open => mortalkin::InteractionKind::Open,
+        InteractionKind::PickUp => mortalkin::InteractionKind::PickUp,
+        InteractionKind::ReadSign => mortalkin::InteractionKind::ReadSign,
+        InteractionKind::UsePortal => mortalkin::InteractionKind::UsePortal,
+    }
+}
+
+fn prompt(interactable: &Interactable) -> String {
+    let action = match interactable.kind {
+        InteractionKind::Talk => "Talk to",
+        InteractionKind::Open => "Open",
+        InteractionKind::PickUp => "Pick up",
+        InteractionKind::ReadSign => "Read",
+        InteractionKind::UsePortal => "Use",
+    };
+    format!("E: {} {}", action, interactable.name)
+}
+
+#[derive(Default)]
+pub struct Interactions {
+    // The one E would use right now.
+    focus: Option<Entity>,
+    // Target we asked the server about and when, in seconds since startup.
+    pending: Option<(Position, f64)>,
+}
+
+// Portals can also be used with E, from next to them or standing on them.
+pub fn spawn_portals(commands: &mut Commands, data: &MapData) {
+    for portal in data.portals() {
+        commands
+            .spawn()
+            .insert(Interactable {
+                kind: InteractionKind::UsePortal,
+                name: "portal".to_string(),
+            })
+            .insert(Position {
+                x: portal.x,
+                y: portal.y,
+            });
+    }
+}
+
+pub struct InteractionPrompt;
+pub struct PromptText;
+
+pub fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let hidden = Visible {
+        is_visible: false,
+        is_transparent: true,
+    };
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(PROMPT_BOTTOM),
+                    ..Default::default()
+                },
+                size: Size::new(Val::Percent(100.), Val::Auto),
+                justify_content: JustifyContent::Center,
+                ..Default::default()
+            },
+            material: materials.add(Color::NONE.into()),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        padding: Rect::all(Val::Px(6.)),
+                        ..Default::default()
+                    },
+                    material: materials.add(Color::rgba(0., 0., 0., 0.6).into()),
+                    visible: hidden.clone(),
+                    ..Default::default()
+                })
+                .insert(InteractionPrompt)
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle(TextBundle {
+                            text: Text::with_section(
+                                "",
+                                TextStyle {
+                                    font: asset_server.load("fonts/FiraSans-Bold.otf"),
+                                    font_size: FONT_SIZE,
+                                    color: Color::WHITE,
+                                },
+                                Default::default(),
+                            ),
+                            visible: hidden,
+                            ..Default::default()
+                        })
+                        .insert(PromptText);
+                });
+        });
+}
+
+fn adjacent(a: &Position, b: &Position) -> bool {
+    (a.x - b.x).abs() <= 1 && (a.y - b.y).abs() <= 1
+}
+
+// Picks the closest interactable next to us and shows what E would do with it.
+pub fn detect(
+    current_char: Res<Character>,
+    chat: Res<Chat>,
+    dialogue: Res<Dialogue>,
+    mut interactions: ResMut<Interactions>,
+    player_query: Query<(&Player, &Position)>,
+    interactable_query: Query<(Entity, &Interactable, &Position)>,
+    mut text_query: Query<&mut Text, With<PromptText>>,
+    mut visible_query: Query<&mut Visible, Or<(With<InteractionPrompt>, With<PromptText>)>>,
+) {
+    let ours = player_query
+        .iter()
+        .find(|(player, _)| player.id == current_char.id)
+        .map(|(_, position)| *position);
+
+    let focus = ours.and_then(|ours| {
+        interactable_query
+            .iter()
+            .filter(|(_, _, position)| adjacent(&ours, position))
+            .min_by_key(|(_, _, position)| {
+                (position.x - ours.x).pow(2) + (position.y - ours.y).pow(2)
+            })
+    });
+    interactions.focus = focus.map(|(entity, _, _)| entity);
+
+    let show = focus.is_some() && !chat.typing && !dialogue.is_open();
+    for mut visible in visible_query.iter_mut() {
+        visible.is_visible = show;
+    }
+    if let Some((_, interactable, _)) = focus {
+        let text = prompt(interactable);
+        for mut prompt_text in text_query.iter_mut() {
+            if prompt_text.sections[0].value != text {
+                prompt_text.sections[0].value = text.clone();
+            }
+        }
+    }
+}
+
+// E asks the server to use whatever has the focus. Runs before
+// `dialogue::input` so the key that closes a dialogue doesn't open it again.
+pub fn interact(
+    time: Res<Time>,
+    keyboard_input: Res<Input<KeyCode>>,
+    chat: Res<Chat>,
+    dialogue: Res<Dialogue>,
+    respawn: Res<Respawn>,
+    current_char: Res<Character>,
+    request_sender: Res<RequestSender>,
+    token: Res<Token>,
+    mut interactions: ResMut<Interactions>,
+    query: Query<(&Interactable, &Position)>,
+) {
+    if chat.typing
+        || dialogue.is_open()
+        || respawn.is_dead()
+        || !keyboard_input.just_pressed(KeyCode::E)
+    {
+        return;
+    }
+    let now = time.seconds_since_startup();
+    if let Some((_, since)) = interactions.pending {
+        if now - since < PENDING_SECONDS {
+            return;
+        }
+    }
+    let (interactable, position) =
+        match interactions.focus.and_then(|entity| query.get(entity).ok()) {
+            Some(found) => found,
+            None => return,
+        };
+
+    request_sender
+        .tx
+        .lock()
+        .unwrap()
+        .unbounded_send(mortalkin::PlayGamePayload {
+            token: token.token.clone(),
+            character_id: current_char.id,
+            position: None,
+            enter_portal: None,
+            chat: None,
+            emote: None,
+            interact: Some(mortalkin::Interact {
+                kind: to_proto(interactable.kind) as i32,
+                target: Some(mortalkin::Position {
+                    x: position.x,
+                    y: position.y,
+                    zone_id: current_char.zone_id,
+                }),
+            }),
+            attack: None,
+        })
+        .unwrap();
+    interactions.pending = Some((*position, now));
+}
+
+// Applies what the server decided. Opening and picking up change the map for
+// everyone in the zone, talking and reading only for whoever asked.
+pub fn resolve(
+    mut commands: Commands,
+    current_char: Res<Character>,
+    mut results: EventReader<mortalkin::InteractionResult>,
+    mut game_state: ResMut<GameMap>,
+    mut interactions: ResMut<Interactions>,
+    mut chat: ResMut<Chat>,
+    mut dialogue: ResMut<Dialogue>,
+    mut query: Query<(
+        Entity,
+        &Interactable,
+        &Position,
+        Option<&Npc>,
+        Option<&Sign>,
+        Option<&Prop>,
+        Option<&mut Visible>,
+    )>,
+) {
+    for result in results.iter() {
+        let ours = result.character_id == current_char.id;
+        let target = match &result.target {
+            Some(target) => target,
+            None => continue,
+        };
+        if ours {
+            interactions.pending = None;
+        }
+        if !result.allowed {
+            if ours {
+                let message = if result.message.is_empty() {
+                    "You can't do that.".to_string()
+                } else {
+                    result.message.clone()
+                };
+                chat.system(message);
+            }
+            continue;
+        }
+        if target.zone_id != current_char.zone_id {
+            continue;
+        }
+
+        for (entity, interactable, position, npc, sign, prop, visible) in query.iter_mut() {
+            if position.x != target.x || position.y != target.y {
+                continue;
+            }
+            if to_proto(interactable.kind) as i32 != result.kind {
+                continue;
+            }
+
+            let footprint = prop.map_or(Vec::new(), |prop| {
+                game_state.data.prop_kinds[&prop.kind].footprint.clone()
+            });
+            match interactable.kind {
+                InteractionKind::Talk if ours => {
+                    let npc = match npc {
+                        Some(npc) => npc,
+                        None => continue,
+                    };
+                    if let Some(file) = &npc.dialogue {
+                        match DialogueTree::load(file) {
+                            Ok(tree) => dialogue.start(tree, &npc.name),
+                            Err(err) => {
+                                chat.system(format!("Can't read dialogue {}: {}", file, err))
+                            }
+                        }
+                    }
+                }
+                InteractionKind::ReadSign if ours => {
+                    if let Some(sign) = sign {
+                        dialogue.start(DialogueTree::line(&sign.text), &interactable.name);
+                    }
+                }
+                InteractionKind::Open => {
+                    commands.entity(entity).remove::<Interactable>();
+                    let passage =
+                        prop.map_or(false, |prop| game_state.data.prop_kinds[&prop.kind].passage);
+                    if passage {
+                        if let Some(mut visible) = visible {
+                            visible.is_visible = false;
+                        }
+                        for (dx, dy) in footprint.iter() {
+                            game_state.collision.set_walkable(
+                                position.x + dx,
+                                position.y + dy,
+                                true,
+                            );
+                        }
+                    }
+                }
+                InteractionKind::PickUp => {
+                    commands.entity(entity).despawn_recursive();
+                    for (dx, dy) in footprint.iter() {
+                        game_state
+                            .collision
+                            .set_walkable(position.x + dx, position.y + dy, true);
+                    }
+                }
+                // The server moves us to the other zone like stepping on it.
+                _ => {}
+            }
+        }
+
+        if ours && !result.message.is_empty() {
+            chat.system(result.message.clone());
+        }
+    }
+}
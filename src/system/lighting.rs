@@ -0,0 +1,316 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::camera::FieldCamera;
+use super::map::LightKind;
+use super::GameMap;
+use super::Position;
+use super::TILE_SIZE;
+
+use bevy::{
+    prelude::*,
+    render::{
+        camera::OrthographicProjection,
+        pipeline::{BlendFactor, BlendOperation, BlendState, PipelineDescriptor, RenderPipeline},
+        texture::{Extent3d, TextureDimension, TextureFormat},
+    },
+    sprite::build_sprite_pipeline,
+};
+
+// Real seconds in a day of world time, a world minute per second.
+const DAY_SECONDS: f64 = 24. * 60.;
+// Above the characters and props, under the fog.
+const TINT_Z: f32 = 150.;
+const LIGHT_Z: f32 = 160.;
+const GLOW_SIZE: u32 = 64;
+
+const NIGHT: [f32; 3] = [0.22, 0.26, 0.48];
+const DAY: [f32; 3] = [1., 1., 1.];
+// Colour the field is multiplied with and how bright the lights are, from
+// midnight at 0 to the next midnight at 1.
+const KEYFRAMES: [(f32, [f32; 3], f32); 8] = [
+    (0., NIGHT, 1.),
+    (0.2, NIGHT, 1.),
+    (0.25, [0.85, 0.6, 0.55], 0.4),
+    (0.32, DAY, 0.),
+    (0.7, DAY, 0.),
+    (0.77, [0.95, 0.62, 0.45], 0.4),
+    (0.83, NIGHT, 1.),
+    (1., NIGHT, 1.),
+];
+
+fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as i64
+}
+
+// Time of day shared by every client, taken from the server's clock.
+#[derive(Default)]
+pub struct WorldClock {
+    // Server clock minus ours, in milliseconds.
+    offset: i64,
+}
+
+impl WorldClock {
+    pub fn sync(&mut self, server_time: u64) {
+        self.offset = server_time as i64 - now_millis();
+    }
+
+    // 0 is midnight, 0.5 noon.
+    pub fn time_of_day(&self) -> f32 {
+        let seconds = (now_millis() + self.offset) as f64 / 1000.;
+        (seconds.rem_euclid(DAY_SECONDS) / DAY_SECONDS) as f32
+    }
+}
+
+// The server's clock in milliseconds since the unix epoch, it comes with
+// every notification.
+pub struct ServerTime(pub u64);
+
+pub fn sync_clock(mut events: EventReader<ServerTime>, mut clock: ResMut<WorldClock>) {
+    if let Some(ServerTime(server_time)) = events.iter().last() {
+        clock.sync(*server_time);
+    }
+}
+
+fn keyframe_at(time_of_day: f32) -> ([f32; 3], f32) {
+    for pair in KEYFRAMES.windows(2) {
+        let (start, from_tint, from_light) = pair[0];
+        let (end, to_tint, to_light) = pair[1];
+        if time_of_day > end {
+            continue;
+        }
+
+        let t = (time_of_day - start) / (end - start);
+        let mut tint = [0.; 3];
+        for (channel, value) in tint.iter_mut().enumerate() {
+            *value = from_tint[channel] + (to_tint[channel] - from_tint[channel]) * t;
+        }
+        return (tint, from_light + (to_light - from_light) * t);
+    }
+
+    (NIGHT, 1.)
+}
+
+impl LightKind {
+    fn colour(&self) -> Color {
+        match self {
+            LightKind::Torch => Color::rgb(1., 0.6, 0.25),
+            LightKind::Window => Color::rgb(1., 0.85, 0.5),
+        }
+    }
+
+    // In tiles.
+    fn radius(&self) -> f32 {
+        match self {
+            LightKind::Torch => 3.5,
+            LightKind::Window => 2.5,
+        }
+    }
+}
+
+// Both the tint and the lights are plain sprites drawn with a different
+// blend, a full screen quad and one quad per light is all it costs.
+pub struct Lighting {
+    multiply: Handle<PipelineDescriptor>,
+    additive: Handle<PipelineDescriptor>,
+    tint: Handle<ColorMaterial>,
+    torch: Handle<ColorMaterial>,
+    window: Handle<ColorMaterial>,
+    lights_spawned: bool,
+}
+
+impl Lighting {
+    fn material(&self, kind: LightKind) -> Handle<ColorMaterial> {
+        match kind {
+            LightKind::Torch => self.torch.clone(),
+            LightKind::Window => self.window.clone(),
+        }
+    }
+}
+
+// White in the middle, fading out to the edge.
+fn glow_texture() -> Texture {
+    let mut texture = Texture::new_fill(
+        Extent3d::new(GLOW_SIZE, GLOW_SIZE, 1),
+        TextureDimension::D2,
+        &[255, 255, 255, 0],
+        TextureFormat::Rgba8UnormSrgb,
+    );
+    let half = GLOW_SIZE as f32 / 2.;
+    for y in 0..GLOW_SIZE {
+        for x in 0..GLOW_SIZE {
+            let offset = Vec2::new(x as f32 + 0.5 - half, y as f32 + 0.5 - half);
+            let falloff = (1. - offset.length() / half).max(0.);
+            let index = ((y * GLOW_SIZE + x) * 4 + 3) as usize;
+            texture.data[index] = (falloff * falloff * 255.) as u8;
+        }
+    }
+    texture
+}
+
+impl FromWorld for Lighting {
+    fn from_world(world: &mut World) -> Self {
+        let (multiply, additive) = {
+            let mut shaders = world.get_resource_mut::<Assets<Shader>>().unwrap();
+            let mut multiply = build_sprite_pipeline(&mut shaders);
+            let mut additive = multiply.clone();
+            // The field times the tint.
+            multiply.color_target_states[0].color_blend = BlendState {
+                src_factor: BlendFactor::Zero,
+                dst_factor: BlendFactor::SrcColor,
+                operation: BlendOperation::Add,
+            };
+            // The field plus the light.
+            additive.color_target_states[0].color_blend = BlendState {
+                src_factor: BlendFactor::SrcAlpha,
+                dst_factor: BlendFactor::One,
+                operation: BlendOperation::Add,
+            };
+            (multiply, additive)
+        };
+        let (multiply, additive) = {
+            let mut pipelines = world
+                .get_resource_mut::<Assets<PipelineDescriptor>>()
+                .unwrap();
+            (pipelines.add(multiply), pipelines.add(additive))
+        };
+        let glow = {
+            let mut textures = world.get_resource_mut::<Assets<Texture>>().unwrap();
+            textures.add(glow_texture())
+        };
+
+        let mut materials = world.get_resource_mut::<Assets<ColorMaterial>>().unwrap();
+        let tint = materials.add(Color::WHITE.into());
+        let torch = materials.add(ColorMaterial {
+            color: LightKind::Torch.colour(),
+            texture: Some(glow.clone()),
+        });
+        let window = materials.add(ColorMaterial {
+            color: LightKind::Window.colour(),
+            texture: Some(glow),
+        });
+
+        Self {
+            multiply,
+            additive,
+            tint,
+            torch,
+            window,
+            lights_spawned: false,
+        }
+    }
+}
+
+pub struct DayTint;
+
+pub struct PointLight {
+    kind: LightKind,
+    position: Position,
+}
+
+fn blended_sprite(
+    pipeline: &Handle<PipelineDescriptor>,
+    material: Handle<ColorMaterial>,
+) -> SpriteBundle {
+    SpriteBundle {
+        material,
+        render_pipelines: RenderPipelines::from_pipelines(vec![RenderPipeline::new(
+            pipeline.clone(),
+        )]),
+        ..Default::default()
+    }
+}
+
+pub fn setup(mut commands: Commands, lighting: Res<Lighting>) {
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite::new(Vec2::ONE),
+            transform: Transform::from_translation(Vec3::new(0., 0., TINT_Z)),
+            ..blended_sprite(&lighting.multiply, lighting.tint.clone())
+        })
+        .insert(DayTint);
+}
+
+pub fn spawn_lights(
+    mut commands: Commands,
+    mut lighting: ResMut<Lighting>,
+    game_state: Res<GameMap>,
+) {
+    if !game_state.map_loaded {
+        lighting.lights_spawned = false;
+        return;
+    }
+    if lighting.lights_spawned {
+        return;
+    }
+
+    for light in game_state.data.lights.iter() {
+        let position = Position {
+            x: light.x,
+            y: light.y,
+        };
+        commands
+            .spawn_bundle(SpriteBundle {
+                transform: Transform::from_translation(position.to_world().extend(LIGHT_Z)),
+                ..blended_sprite(&lighting.additive, lighting.material(light.kind))
+            })
+            .insert(PointLight {
+                kind: light.kind,
+                position,
+            });
+    }
+    lighting.lights_spawned = true;
+}
+
+pub fn update(
+    time: Res<Time>,
+    windows: Res<Windows>,
+    clock: Res<WorldClock>,
+    lighting: Res<Lighting>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    camera_query: Query<(&Transform, &OrthographicProjection), With<FieldCamera>>,
+    mut tint_query: Query<(&mut Transform, &mut Sprite), (With<DayTint>, Without<FieldCamera>)>,
+    mut light_query: Query<(&PointLight, &mut Transform), (Without<DayTint>, Without<FieldCamera>)>,
+) {
+    let (tint, light) = keyframe_at(clock.time_of_day());
+    if let Some(material) = materials.get_mut(&lighting.tint) {
+        material.color = Color::rgb(tint[0], tint[1], tint[2]);
+    }
+    for kind in [LightKind::Torch, LightKind::Window].iter() {
+        if let Some(material) = materials.get_mut(&lighting.material(*kind)) {
+            material.color.set_a(light);
+        }
+    }
+
+    // The tint covers whatever the camera sees, with a margin so it doesn't
+    // trail behind a moving camera.
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    if let Some((camera, projection)) = camera_query.iter().next() {
+        let size = Vec2::new(window.width(), window.height()) * projection.scale
+            + Vec2::splat(TILE_SIZE * 4.);
+        for (mut transform, mut sprite) in tint_query.iter_mut() {
+            transform.translation.x = camera.translation.x;
+            transform.translation.y = camera.translation.y;
+            sprite.size = size;
+        }
+    }
+
+    // Torches flicker by changing size, so they can keep sharing a material.
+    let seconds = time.seconds_since_startup() as f32;
+    for (point_light, mut transform) in light_query.iter_mut() {
+        let flicker = match point_light.kind {
+            LightKind::Torch => {
+                let phase = (point_light.position.x * 7 + point_light.position.y * 13) as f32;
+                1. + (seconds * 9. + phase).sin() * 0.04 + (seconds * 23. + phase).sin() * 0.02
+            }
+            LightKind::Window => 1.,
+        };
+        let scale = point_light.kind.radius() * 2. * TILE_SIZE / GLOW_SIZE as f32 * flicker;
+        transform.scale = Vec3::new(scale, scale, 1.);
+    }
+}
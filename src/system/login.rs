@@ -277,6 +277,10 @@ pub fn login_system(
                             id: character.id,
                             name: character.name.clone(),
                             position: None,
+                            zone_id: character
+                                .position
+                                .as_ref()
+                                .map_or(0, |position| position.zone_id),
                         })
                         .collect();
 
@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+use std::fs;
+
+use serde::{Deserialize, Serialize};
+
+// Every zone has its own map file, named after the zone id.
+pub fn zone_path(zone_id: u32) -> String {
+    format!("assets/map/zone{}.ron", zone_id)
+}
+
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct Frame {
+    // Path of the texture inside the `texture` asset folder.
+    pub texture: String,
+    // Seconds to show this frame for, only used by animated tiles.
+    #[serde(default)]
+    pub duration: f32,
+}
+
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct TileKind {
+    // A single frame is a static tile, more than one loops through them.
+    pub frames: Vec<Frame>,
+    #[serde(default = "walkable_by_default")]
+    pub walkable: bool,
+}
+
+impl TileKind {
+    pub fn is_animated(&self) -> bool {
+        self.frames.len() > 1
+    }
+}
+
+fn walkable_by_default() -> bool {
+    true
+}
+
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct PlacedTile {
+    pub x: i32,
+    pub y: i32,
+    pub kind: String,
+}
+
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct PropKind {
+    pub texture: String,
+    // Pixel of the texture, counted from its bottom-left corner, that stands
+    // on the bottom edge of the prop's tile. Defaults to the bottom centre.
+    #[serde(default)]
+    pub anchor: Option<(f32, f32)>,
+    // Tiles the prop blocks, relative to the tile it's placed on.
+    #[serde(default)]
+    pub footprint: Vec<(i32, i32)>,
+    // Tiles the prop lets you walk on, like a bridge over water.
+    #[serde(default)]
+    pub walkable: Vec<(i32, i32)>,
+    // Flat props lie on the ground and are drawn under every character.
+    #[serde(default)]
+    pub flat: bool,
+    // What pressing E next to it does, see `interaction`.
+    #[serde(default)]
+    pub interaction: Option<InteractionKind>,
+    // Opening it clears its footprint and hides it, like a door or a gate.
+    #[serde(default)]
+    pub passage: bool,
+}
+
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct PlacedProp {
+    pub x: i32,
+    pub y: i32,
+    pub kind: String,
+    // What a sign says.
+    #[serde(default)]
+    pub text: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum InteractionKind {
+    Talk,
+    Open,
+    PickUp,
+    ReadSign,
+    UsePortal,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub enum ObjectKind {
+    // Stepping on it asks the server to move the character to `zone`, at
+    // (x, y) over there.
+    Portal { zone: u32, x: i32, y: i32 },
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MapObject {
+    pub x: i32,
+    pub y: i32,
+    pub kind: ObjectKind,
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum LightKind {
+    Torch,
+    Window,
+}
+
+// Glows at night, see `lighting`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PlacedLight {
+    pub x: i32,
+    pub y: i32,
+    pub kind: LightKind,
+}
+
+// Someone to talk to, see `npc`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PlacedNpc {
+    pub x: i32,
+    pub y: i32,
+    pub name: String,
+    pub texture: String,
+    // File in `assets/dialogue`, without the extension.
+    #[serde(default)]
+    pub dialogue: Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TerrainGenerator {
+    // Comes from the server with the map, so every client generates the same
+    // terrain.
+    pub seed: u64,
+}
+
+fn default_spawn() -> (i32, i32) {
+    (2, 2)
+}
+
+// Maps are centred on (0, 0): tiles go from -width / 2 to width / 2 - 1.
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct MapData {
+    pub width: i32,
+    pub height: i32,
+    // Kind of every tile that isn't listed in `tiles`.
+    pub fill: String,
+    pub kinds: HashMap<String, TileKind>,
+    pub tiles: Vec<PlacedTile>,
+    // Tiles nobody can walk on whatever their kind, painted in the editor.
+    #[serde(default)]
+    pub blocked: Vec<(i32, i32)>,
+    #[serde(default)]
+    pub prop_kinds: HashMap<String, PropKind>,
+    #[serde(default)]
+    pub props: Vec<PlacedProp>,
+    #[serde(default)]
+    pub objects: Vec<MapObject>,
+    #[serde(default)]
+    pub lights: Vec<PlacedLight>,
+    #[serde(default)]
+    pub npcs: Vec<PlacedNpc>,
+    // Outdoor maps get puddles when it rains, see `weather`.
+    #[serde(default)]
+    pub puddles: bool,
+    // Where characters entering the zone without a destination appear.
+    #[serde(default = "default_spawn")]
+    pub spawn: (i32, i32),
+    // Generated terrain replaces the fill, see `terrain`.
+    #[serde(default)]
+    pub generator: Option<TerrainGenerator>,
+}
+
+impl MapData {
+    pub fn load(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = fs::read_to_string(path)?;
+        Ok(ron::de::from_str(&content)?)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let content = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?;
+        fs::write(path, content)?;
+        Ok(())
+    }
+
+    pub fn portals(&self) -> impl Iterator<Item = &MapObject> {
+        self.objects
+            .iter()
+            .filter(|object| matches!(object.kind, ObjectKind::Portal { .. }))
+    }
+
+    pub fn portal_at(&self, x: i32, y: i32) -> Option<&MapObject> {
+        self.portals().find(|portal| portal.x == x && portal.y == y)
+    }
+
+    pub fn min_x(&self) -> i32 {
+        -self.width / 2
+    }
+
+    pub fn min_y(&self) -> i32 {
+        -self.height / 2
+    }
+}
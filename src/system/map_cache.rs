@@ -0,0 +1,246 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::mpsc;
+use std::sync::Mutex;
+
+use crate::pursuit::api::mortalkin::{GetMapPayload, GetMapResponse};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+const CACHE_DIR: &str = "cache/maps";
+const INDEX_FILE: &str = "index.ron";
+// Where the game server, which serves the maps too, listens.
+const DEFAULT_SERVER: &str = "http://[::1]:5004";
+
+// `--map-server <url>` gets the maps from somewhere else, like
+// `examples/mock_map_server.rs`.
+pub fn server_from_args() -> String {
+    let mut args = std::env::args().skip_while(|arg| arg != "--map-server");
+    args.next();
+    args.next().unwrap_or_else(|| DEFAULT_SERVER.to_string())
+}
+
+pub struct MapRequestSender {
+    pub tx: Mutex<mpsc::Sender<GetMapPayload>>,
+}
+
+pub struct MapResponseReceiver {
+    pub rx: Mutex<mpsc::Receiver<Result<tonic::Response<GetMapResponse>, tonic::Status>>>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct CachedMap {
+    version: u32,
+    // Hex sha256 of the content, also the name of its file in the cache.
+    hash: String,
+}
+
+// Which version of every zone's map is on disk.
+#[derive(Default, Serialize, Deserialize)]
+pub struct CacheIndex {
+    // Holds the index and the maps, `CACHE_DIR` unless a test says otherwise.
+    #[serde(skip)]
+    dir: String,
+    zones: HashMap<u32, CachedMap>,
+}
+
+impl CacheIndex {
+    pub fn load() -> Self {
+        Self::load_from(CACHE_DIR)
+    }
+
+    pub fn load_from(dir: &str) -> Self {
+        let mut index: Self = fs::read_to_string(format!("{}/{}", dir, INDEX_FILE))
+            .ok()
+            .and_then(|content| ron::de::from_str(&content).ok())
+            .unwrap_or_default();
+        index.dir = dir.to_string();
+        index
+    }
+
+    fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let content = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?;
+        fs::write(format!("{}/{}", self.dir, INDEX_FILE), content)?;
+        Ok(())
+    }
+
+    // 0 when nothing usable is cached, so the server always sends the map.
+    pub fn version(&self, zone_id: u32) -> u32 {
+        match self.path(zone_id) {
+            Some(_) => self.zones[&zone_id].version,
+            None => 0,
+        }
+    }
+
+    pub fn path(&self, zone_id: u32) -> Option<String> {
+        let cached = self.zones.get(&zone_id)?;
+        let path = self.content_path(&cached.hash);
+        if Path::new(&path).exists() {
+            Some(path)
+        } else {
+            None
+        }
+    }
+
+    pub fn store(
+        &mut self,
+        zone_id: u32,
+        version: u32,
+        hash: &str,
+        content: &[u8],
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        // Whatever got cut short or mangled on the way doesn't get cached.
+        if format!("{:x}", Sha256::digest(content)) != hash {
+            return Err(format!("map {} doesn't match its hash", zone_id).into());
+        }
+
+        let path = self.content_path(hash);
+        fs::create_dir_all(&self.dir)?;
+        fs::write(&path, content)?;
+
+        self.zones.insert(
+            zone_id,
+            CachedMap {
+                version,
+                hash: hash.to_string(),
+            },
+        );
+        self.save()?;
+        Ok(path)
+    }
+
+    // Drops a zone whose cached map turned out not to load.
+    pub fn forget(&mut self, zone_id: u32) -> Result<(), Box<dyn std::error::Error>> {
+        let cached = match self.zones.remove(&zone_id) {
+            Some(cached) => cached,
+            None => return Ok(()),
+        };
+
+        let path = self.content_path(&cached.hash);
+        if Path::new(&path).exists() {
+            fs::remove_file(path)?;
+        }
+        self.save()
+    }
+
+    fn content_path(&self, hash: &str) -> String {
+        format!("{}/{}.ron", self.dir, hash)
+    }
+}
+
+// Picks the file to load a zone from once the server answered: what it just
+// sent, the cached copy when ours is current or the server can't be reached,
+// and the map shipped with the client as a last resort.
+pub fn resolve(
+    index: &mut CacheIndex,
+    zone_id: u32,
+    response: Result<tonic::Response<GetMapResponse>, tonic::Status>,
+) -> String {
+    if let Ok(response) = response {
+        let response = response.into_inner();
+        if !response.content.is_empty() {
+            let stored = index.store(zone_id, response.version, &response.hash, &response.content);
+            if let Ok(path) = stored {
+                return path;
+            }
+        }
+    }
+
+    fallback(index, zone_id)
+}
+
+// The cached copy, or the map shipped with the client when there's none.
+pub fn fallback(index: &CacheIndex, zone_id: u32) -> String {
+    index
+        .path(zone_id)
+        .unwrap_or_else(|| super::map::zone_path(zone_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash(content: &[u8]) -> String {
+        format!("{:x}", Sha256::digest(content))
+    }
+
+    // Every test gets an empty cache of its own.
+    fn cache_dir(name: &str) -> String {
+        let dir =
+            std::env::temp_dir().join(format!("bev-map-cache-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir.to_string_lossy().into_owned()
+    }
+
+    fn response(
+        version: u32,
+        hash: String,
+        content: &[u8],
+    ) -> Result<tonic::Response<GetMapResponse>, tonic::Status> {
+        Ok(tonic::Response::new(GetMapResponse {
+            zone_id: 7,
+            version,
+            hash,
+            content: content.to_vec(),
+        }))
+    }
+
+    #[test]
+    fn store_rejects_bad_hash() {
+        let mut index = CacheIndex::load_from(&cache_dir("bad-hash"));
+        let content = b"(width: 2, height: 2)";
+        let stored = index.store(7, 3, &hash(b"something else"), content);
+        assert!(stored.is_err());
+        assert_eq!(index.version(7), 0);
+        assert!(index.path(7).is_none());
+    }
+
+    #[test]
+    fn bad_download_falls_back_to_bundled_map() {
+        let mut index = CacheIndex::load_from(&cache_dir("bad-download"));
+        let cut_short = response(3, hash(b"the whole map"), b"the whole m");
+        let path = resolve(&mut index, 7, cut_short);
+        assert_eq!(path, super::super::map::zone_path(7));
+    }
+
+    #[test]
+    fn unreachable_server_falls_back_to_bundled_map() {
+        let mut index = CacheIndex::load_from(&cache_dir("unreachable"));
+        let path = resolve(&mut index, 7, Err(tonic::Status::unavailable("down")));
+        assert_eq!(path, super::super::map::zone_path(7));
+    }
+
+    #[test]
+    fn store_hit_and_download_again() {
+        let dir = cache_dir("flow");
+        let mut index = CacheIndex::load_from(&dir);
+        assert_eq!(index.version(7), 0);
+
+        let first = b"(width: 3, height: 3)";
+        let path = resolve(&mut index, 7, response(3, hash(first), first));
+        assert_eq!(fs::read(&path).unwrap(), first);
+
+        // The index is saved, a restarted client asks for what it has.
+        let mut index = CacheIndex::load_from(&dir);
+        assert_eq!(index.version(7), 3);
+        assert_eq!(resolve(&mut index, 7, response(3, hash(first), b"")), path);
+
+        // A newer map that got mangled on the way leaves the cached one.
+        let second = b"(width: 4, height: 4)";
+        let mangled = response(4, hash(second), b"(width: 4, heig");
+        assert_eq!(resolve(&mut index, 7, mangled), path);
+        assert_eq!(index.version(7), 3);
+
+        // Once the cached map is dropped the server sends it again.
+        index.forget(7).unwrap();
+        assert_eq!(index.version(7), 0);
+        assert!(!Path::new(&path).exists());
+        let path = resolve(&mut index, 7, response(4, hash(second), second));
+        assert_eq!(fs::read(&path).unwrap(), second);
+        assert_eq!(CacheIndex::load_from(&dir).version(7), 4);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,504 @@
+use std::collections::HashMap;
+use std::fs;
+
+use super::chat::Chat;
+use super::picking::UiPanel;
+use super::Character;
+use super::GameMap;
+use super::Player;
+use super::Position;
+
+use bevy::{
+    input::mouse::MouseWheel,
+    prelude::*,
+    render::texture::{Extent3d, TextureDimension, TextureFormat},
+};
+
+// Tiles across the minimap, one texture pixel each.
+const MINIMAP_TILES: i32 = 96;
+// Screen pixels per minimap tile.
+const MINIMAP_SCALE: f32 = 2.;
+// The world map texture is never wider or taller than this, big maps are
+// sampled every few tiles.
+const WORLD_MAP_MAX: i32 = 512;
+const REFRESH_SECONDS: f32 = 0.1;
+const WORLD_MAP_MIN_ZOOM: f32 = 0.5;
+const WORLD_MAP_MAX_ZOOM: f32 = 8.;
+const WAYPOINTS_DIR: &str = "cache/waypoints";
+
+const OUTSIDE: [u8; 4] = [16, 16, 24, 255];
+const SELF_MARKER: [u8; 4] = [255, 255, 255, 255];
+const REMOTE_MARKER: [u8; 4] = [230, 60, 60, 255];
+const PORTAL_MARKER: [u8; 4] = [190, 90, 255, 255];
+const WAYPOINT_MARKER: [u8; 4] = [255, 210, 40, 255];
+
+// Colours of the loaded map, one per tile kind, averaged from its texture.
+pub struct Minimap {
+    texture: Handle<Texture>,
+    world_texture: Handle<Texture>,
+    timer: Timer,
+    ready: bool,
+    fill: [u8; 4],
+    tiles: HashMap<(i32, i32), [u8; 4]>,
+    // Tiles per world map pixel on each axis.
+    world_step: i32,
+    // The world map without the waypoints drawn on it.
+    world_base: Vec<u8>,
+}
+
+impl Default for Minimap {
+    fn default() -> Self {
+        Self {
+            texture: Default::default(),
+            world_texture: Default::default(),
+            timer: Timer::from_seconds(REFRESH_SECONDS, true),
+            ready: false,
+            fill: OUTSIDE,
+            tiles: HashMap::new(),
+            world_step: 1,
+            world_base: Vec::new(),
+        }
+    }
+}
+
+impl Minimap {
+    fn colour_at(&self, game_state: &GameMap, x: i32, y: i32) -> [u8; 4] {
+        let data = &game_state.data;
+        if x < data.min_x()
+            || y < data.min_y()
+            || x >= data.min_x() + data.width
+            || y >= data.min_y() + data.height
+        {
+            return OUTSIDE;
+        }
+
+        *self.tiles.get(&(x, y)).unwrap_or(&self.fill)
+    }
+}
+
+pub struct WorldMap {
+    pub open: bool,
+    zoom: f32,
+    // Screen pixels the map is dragged away from the middle of the window.
+    pan: Vec2,
+    last_cursor: Option<Vec2>,
+}
+
+impl Default for WorldMap {
+    fn default() -> Self {
+        Self {
+            open: false,
+            zoom: 1.,
+            pan: Vec2::ZERO,
+            last_cursor: None,
+        }
+    }
+}
+
+// Markers the player put on the world map, kept on disk per character.
+#[derive(Default)]
+pub struct Waypoints {
+    zones: HashMap<u32, Vec<(i32, i32)>>,
+}
+
+impl Waypoints {
+    fn path(character_id: u32) -> String {
+        format!("{}/{}.ron", WAYPOINTS_DIR, character_id)
+    }
+
+    pub fn load(character_id: u32) -> Self {
+        let zones = fs::read_to_string(Self::path(character_id))
+            .ok()
+            .and_then(|content| ron::de::from_str(&content).ok())
+            .unwrap_or_default();
+        Self { zones }
+    }
+
+    pub fn save(&self, character_id: u32) -> Result<(), Box<dyn std::error::Error>> {
+        let content = ron::ser::to_string_pretty(&self.zones, ron::ser::PrettyConfig::default())?;
+        fs::create_dir_all(WAYPOINTS_DIR)?;
+        fs::write(Self::path(character_id), content)?;
+        Ok(())
+    }
+
+    fn toggle(&mut self, zone_id: u32, point: (i32, i32)) {
+        let waypoints = self.zones.entry(zone_id).or_insert_with(Vec::new);
+        match waypoints.iter().position(|waypoint| *waypoint == point) {
+            Some(index) => {
+                waypoints.remove(index);
+            }
+            None => waypoints.push(point),
+        }
+    }
+}
+
+pub struct MinimapImage;
+pub struct WorldMapRoot;
+pub struct WorldMapImage;
+
+fn new_texture(width: i32, height: i32) -> Texture {
+    Texture::new_fill(
+        Extent3d::new(width as u32, height as u32, 1),
+        TextureDimension::D2,
+        &OUTSIDE,
+        TextureFormat::Rgba8UnormSrgb,
+    )
+}
+
+fn put_pixel(data: &mut [u8], width: i32, height: i32, x: i32, y: i32, colour: [u8; 4]) {
+    if x < 0 || y < 0 || x >= width || y >= height {
+        return;
+    }
+
+    let index = ((y * width + x) * 4) as usize;
+    data[index..index + 4].copy_from_slice(&colour);
+}
+
+// A square marker centred on a pixel, so single tiles stay visible.
+fn put_marker(data: &mut [u8], width: i32, height: i32, x: i32, y: i32, colour: [u8; 4]) {
+    for dy in -1..=1 {
+        for dx in -1..=1 {
+            put_pixel(data, width, height, x + dx, y + dy, colour);
+        }
+    }
+}
+
+fn average_colour(texture: &Texture) -> [u8; 4] {
+    let mut total = [0u64; 3];
+    let mut count = 0;
+    for pixel in texture.data.chunks(4) {
+        if pixel[3] == 0 {
+            continue;
+        }
+        for (total, value) in total.iter_mut().zip(pixel) {
+            *total += *value as u64;
+        }
+        count += 1;
+    }
+    if count == 0 {
+        return OUTSIDE;
+    }
+
+    [
+        (total[0] / count) as u8,
+        (total[1] / count) as u8,
+        (total[2] / count) as u8,
+        255,
+    ]
+}
+
+pub fn setup(
+    mut commands: Commands,
+    mut minimap: ResMut<Minimap>,
+    mut textures: ResMut<Assets<Texture>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut waypoints: ResMut<Waypoints>,
+    current_char: Res<Character>,
+) {
+    minimap.texture = textures.add(new_texture(MINIMAP_TILES, MINIMAP_TILES));
+    minimap.world_texture = textures.add(new_texture(1, 1));
+    *waypoints = Waypoints::load(current_char.id);
+
+    let size = MINIMAP_TILES as f32 * MINIMAP_SCALE;
+    commands
+        .spawn_bundle(ImageBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    right: Val::Px(10.),
+                    top: Val::Px(10.),
+                    ..Default::default()
+                },
+                size: Size::new(Val::Px(size), Val::Px(size)),
+                ..Default::default()
+            },
+            material: materials.add(minimap.texture.clone().into()),
+            ..Default::default()
+        })
+        .insert(MinimapImage)
+        .insert(UiPanel);
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                ..Default::default()
+            },
+            material: materials.add(Color::rgba(0., 0., 0., 0.85).into()),
+            visible: Visible {
+                is_visible: false,
+                is_transparent: true,
+            },
+            ..Default::default()
+        })
+        .insert(WorldMapRoot);
+    commands
+        .spawn_bundle(ImageBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                ..Default::default()
+            },
+            material: materials.add(minimap.world_texture.clone().into()),
+            visible: Visible {
+                is_visible: false,
+                is_transparent: true,
+            },
+            ..Default::default()
+        })
+        .insert(WorldMapImage);
+}
+
+// Works out the colours of a freshly built map and draws its world map.
+pub fn read_map(
+    mut minimap: ResMut<Minimap>,
+    mut textures: ResMut<Assets<Texture>>,
+    game_state: Res<GameMap>,
+    asset_server: Res<AssetServer>,
+    waypoints: Res<Waypoints>,
+    current_char: Res<Character>,
+) {
+    if !game_state.map_loaded {
+        minimap.ready = false;
+        return;
+    }
+    if minimap.ready {
+        return;
+    }
+
+    let data = &game_state.data;
+    let mut kind_colours = HashMap::new();
+    for (name, kind) in data.kinds.iter() {
+        let handle: Handle<Texture> = asset_server.get_handle(kind.frames[0].texture.as_str());
+        let colour = textures.get(&handle).map_or(OUTSIDE, average_colour);
+        kind_colours.insert(name.as_str(), colour);
+    }
+    minimap.fill = kind_colours[data.fill.as_str()];
+    minimap.tiles = data
+        .tiles
+        .iter()
+        .map(|placed| ((placed.x, placed.y), kind_colours[placed.kind.as_str()]))
+        .collect();
+
+    let step = ((data.width.max(data.height) + WORLD_MAP_MAX - 1) / WORLD_MAP_MAX).max(1);
+    let width = (data.width + step - 1) / step;
+    let height = (data.height + step - 1) / step;
+    let mut world = new_texture(width, height);
+    for row in 0..height {
+        for column in 0..width {
+            // The top row of the texture is the north edge of the map.
+            let x = data.min_x() + column * step;
+            let y = data.min_y() + data.height - 1 - row * step;
+            let colour = minimap.colour_at(&game_state, x, y);
+            put_pixel(&mut world.data, width, height, column, row, colour);
+        }
+    }
+    for portal in data.portals() {
+        let column = (portal.x - data.min_x()) / step;
+        let row = (data.min_y() + data.height - 1 - portal.y) / step;
+        put_marker(&mut world.data, width, height, column, row, PORTAL_MARKER);
+    }
+
+    minimap.world_step = step;
+    minimap.world_base = world.data.clone();
+    draw_waypoints(&mut world, &minimap, &game_state, &waypoints, &current_char);
+    textures.set(minimap.world_texture.clone(), world);
+    minimap.ready = true;
+}
+
+fn draw_waypoints(
+    world: &mut Texture,
+    minimap: &Minimap,
+    game_state: &GameMap,
+    waypoints: &Waypoints,
+    current_char: &Character,
+) {
+    let data = &game_state.data;
+    let width = world.size.width as i32;
+    let height = world.size.height as i32;
+    world.data.copy_from_slice(&minimap.world_base);
+    if let Some(points) = waypoints.zones.get(&current_char.zone_id) {
+        for (x, y) in points.iter() {
+            let column = (x - data.min_x()) / minimap.world_step;
+            let row = (data.min_y() + data.height - 1 - y) / minimap.world_step;
+            put_marker(&mut world.data, width, height, column, row, WAYPOINT_MARKER);
+        }
+    }
+}
+
+pub fn refresh_minimap(
+    time: Res<Time>,
+    mut minimap: ResMut<Minimap>,
+    mut textures: ResMut<Assets<Texture>>,
+    game_state: Res<GameMap>,
+    current_char: Res<Character>,
+    waypoints: Res<Waypoints>,
+    player_query: Query<(&Player, &Position)>,
+) {
+    if !minimap.timer.tick(time.delta()).just_finished() || !minimap.ready {
+        return;
+    }
+
+    let center = match player_query
+        .iter()
+        .find(|(player, _)| player.id == current_char.id)
+    {
+        Some((_, position)) => *position,
+        None => return,
+    };
+
+    // Pixel of the minimap a tile lands on, the top row is north.
+    let half = MINIMAP_TILES / 2;
+    let pixel = |x: i32, y: i32| (x - center.x + half, center.y + half - y);
+
+    let texture = match textures.get_mut(&minimap.texture) {
+        Some(texture) => texture,
+        None => return,
+    };
+    for row in 0..MINIMAP_TILES {
+        for column in 0..MINIMAP_TILES {
+            let colour =
+                minimap.colour_at(&game_state, center.x - half + column, center.y + half - row);
+            put_pixel(
+                &mut texture.data,
+                MINIMAP_TILES,
+                MINIMAP_TILES,
+                column,
+                row,
+                colour,
+            );
+        }
+    }
+
+    let mut markers = Vec::new();
+    for portal in game_state.data.portals() {
+        markers.push((pixel(portal.x, portal.y), PORTAL_MARKER));
+    }
+    if let Some(points) = waypoints.zones.get(&current_char.zone_id) {
+        for (x, y) in points.iter() {
+            markers.push((pixel(*x, *y), WAYPOINT_MARKER));
+        }
+    }
+    for (player, position) in player_query.iter() {
+        if player.id != current_char.id {
+            markers.push((pixel(position.x, position.y), REMOTE_MARKER));
+        }
+    }
+    // Drawn last so nothing covers it.
+    markers.push((pixel(center.x, center.y), SELF_MARKER));
+
+    for ((x, y), colour) in markers.into_iter() {
+        put_marker(
+            &mut texture.data,
+            MINIMAP_TILES,
+            MINIMAP_TILES,
+            x,
+            y,
+            colour,
+        );
+    }
+}
+
+pub fn toggle_world_map(
+    keyboard_input: Res<Input<KeyCode>>,
+    chat: Res<Chat>,
+    mut world_map: ResMut<WorldMap>,
+    mut query: Query<&mut Visible, Or<(With<WorldMapRoot>, With<WorldMapImage>)>>,
+) {
+    if chat.typing || !keyboard_input.just_pressed(KeyCode::O) {
+        return;
+    }
+
+    world_map.open = !world_map.open;
+    world_map.last_cursor = None;
+    for mut visible in query.iter_mut() {
+        visible.is_visible = world_map.open;
+    }
+}
+
+// Wheel zooms, dragging with the left button pans, right clicking a tile adds
+// a waypoint there or removes the one it has.
+pub fn world_map_input(
+    windows: Res<Windows>,
+    mouse_input: Res<Input<MouseButton>>,
+    mut mouse_wheel_events: EventReader<MouseWheel>,
+    mut world_map: ResMut<WorldMap>,
+    mut waypoints: ResMut<Waypoints>,
+    minimap: Res<Minimap>,
+    game_state: Res<GameMap>,
+    current_char: Res<Character>,
+    mut chat: ResMut<Chat>,
+    mut textures: ResMut<Assets<Texture>>,
+    mut query: Query<&mut Style, With<WorldMapImage>>,
+) {
+    if !world_map.open || !minimap.ready {
+        return;
+    }
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    let window_size = Vec2::new(window.width(), window.height());
+    let cursor = window.cursor_position();
+
+    for event in mouse_wheel_events.iter() {
+        let factor = if event.y > 0. { 1.25 } else { 0.8 };
+        world_map.zoom = (world_map.zoom * factor)
+            .max(WORLD_MAP_MIN_ZOOM)
+            .min(WORLD_MAP_MAX_ZOOM);
+    }
+
+    if mouse_input.pressed(MouseButton::Left) {
+        if let (Some(last), Some(cursor)) = (world_map.last_cursor, cursor) {
+            world_map.pan += cursor - last;
+        }
+        world_map.last_cursor = cursor;
+    } else {
+        world_map.last_cursor = None;
+    }
+
+    let texture_size = match textures.get(&minimap.world_texture) {
+        Some(texture) => Vec2::new(texture.size.width as f32, texture.size.height as f32),
+        None => return,
+    };
+    // Fit the map to the window at zoom 1.
+    let fit = (window_size.x / texture_size.x).min(window_size.y / texture_size.y);
+    let size = texture_size * fit * world_map.zoom;
+    let bottom_left = (window_size - size) / 2. + world_map.pan;
+
+    for mut style in query.iter_mut() {
+        style.size = Size::new(Val::Px(size.x), Val::Px(size.y));
+        style.position.left = Val::Px(bottom_left.x);
+        style.position.bottom = Val::Px(bottom_left.y);
+    }
+
+    if !mouse_input.just_pressed(MouseButton::Right) {
+        return;
+    }
+    let cursor = match cursor {
+        Some(cursor) => cursor,
+        None => return,
+    };
+    let local = (cursor - bottom_left) / size;
+    if local.x < 0. || local.y < 0. || local.x >= 1. || local.y >= 1. {
+        return;
+    }
+
+    let data = &game_state.data;
+    let x = data.min_x() + (local.x * data.width as f32) as i32;
+    let y = data.min_y() + (local.y * data.height as f32) as i32;
+    // Snap to the world map's pixels, or a waypoint could never be clicked
+    // again on a sampled map.
+    let step = minimap.world_step;
+    let x = data.min_x() + (x - data.min_x()) / step * step;
+    let y = data.min_y() + data.height - 1 - (data.min_y() + data.height - 1 - y) / step * step;
+    waypoints.toggle(current_char.zone_id, (x, y));
+    // The waypoint stays for this session even if it can't be written.
+    if let Err(err) = waypoints.save(current_char.id) {
+        chat.system(format!("Can't save waypoints: {}", err));
+    }
+
+    if let Some(world) = textures.get_mut(&minimap.world_texture) {
+        draw_waypoints(world, &minimap, &game_state, &waypoints, &current_char);
+    }
+}
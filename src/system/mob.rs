@@ -0,0 +1,289 @@
+use std::collections::{HashMap, HashSet};
+
+use super::collision::Blocking;
+use super::combat::Dead;
+use super::movement::Motion;
+use super::Character;
+use super::GameMap;
+use super::Health;
+use super::Position;
+use super::PIXEL_SCALE;
+use super::TILE_SIZE;
+use crate::pursuit::api::mortalkin::{self, MobKind};
+
+use bevy::prelude::*;
+
+// Tiles per second, mobs are a bit slower than players.
+const MOB_SPEED: f32 = 4.;
+
+pub struct MobDef {
+    kind: MobKind,
+    pub name: &'static str,
+    texture: &'static str,
+    // The sheets are a single row of frames.
+    frame_width: f32,
+    frame_height: f32,
+    frame_count: usize,
+    fps: f32,
+}
+
+const MOBS: [MobDef; 7] = [
+    MobDef {
+        kind: MobKind::SlimeBlue,
+        name: "Blue slime",
+        texture: "sprite/mobs/slime-blue.png",
+        frame_width: 16.,
+        frame_height: 24.,
+        frame_count: 4,
+        fps: 6.,
+    },
+    MobDef {
+        kind: MobKind::SlimeGreen,
+        name: "Green slime",
+        texture: "sprite/mobs/slime-green.png",
+        frame_width: 16.,
+        frame_height: 24.,
+        frame_count: 4,
+        fps: 6.,
+    },
+    MobDef {
+        kind: MobKind::SlimeOrange,
+        name: "Orange slime",
+        texture: "sprite/mobs/slime-orange.png",
+        frame_width: 16.,
+        frame_height: 24.,
+        frame_count: 4,
+        fps: 6.,
+    },
+    MobDef {
+        kind: MobKind::Fox,
+        name: "Fox",
+        texture: "sprite/mobs/fox-run.png",
+        frame_width: 24.,
+        frame_height: 24.,
+        frame_count: 6,
+        fps: 12.,
+    },
+    MobDef {
+        kind: MobKind::Kobold,
+        name: "Kobold",
+        texture: "sprite/mobs/kobold-idle.png",
+        frame_width: 24.,
+        frame_height: 24.,
+        frame_count: 15,
+        fps: 10.,
+    },
+    MobDef {
+        kind: MobKind::Worm,
+        name: "Worm",
+        texture: "sprite/mobs/worm-run-idle.png",
+        frame_width: 16.,
+        frame_height: 24.,
+        frame_count: 31,
+        fps: 12.,
+    },
+    MobDef {
+        kind: MobKind::BossBee,
+        name: "Queen bee",
+        texture: "sprite/mobs/boss_bee.png",
+        frame_width: 34.,
+        frame_height: 34.,
+        frame_count: 1,
+        fps: 1.,
+    },
+];
+
+pub fn mob_def(kind: MobKind) -> &'static MobDef {
+    MOBS.iter().find(|def| def.kind == kind).unwrap()
+}
+
+pub struct Mob {
+    pub id: u32,
+    pub kind: MobKind,
+}
+
+pub struct MobAnimation {
+    frame: usize,
+    elapsed: f32,
+    last_point: Vec2,
+}
+
+pub struct MobSprites {
+    atlases: HashMap<MobKind, Handle<TextureAtlas>>,
+}
+
+impl FromWorld for MobSprites {
+    fn from_world(world: &mut World) -> Self {
+        let textures: Vec<(MobKind, Handle<Texture>)> = {
+            let asset_server = world.get_resource::<AssetServer>().unwrap();
+            MOBS.iter()
+                .map(|def| (def.kind, asset_server.load(def.texture)))
+                .collect()
+        };
+        let mut texture_atlases = world.get_resource_mut::<Assets<TextureAtlas>>().unwrap();
+        let atlases = textures
+            .into_iter()
+            .map(|(kind, texture)| {
+                let def = mob_def(kind);
+                let atlas = TextureAtlas::from_grid(
+                    texture,
+                    Vec2::new(def.frame_width, def.frame_height),
+                    def.frame_count,
+                    1,
+                );
+                (kind, texture_atlases.add(atlas))
+            })
+            .collect();
+        Self { atlases }
+    }
+}
+
+#[derive(Default)]
+pub struct Mobs {
+    // Updates from the server waiting for the map to be built.
+    received: Vec<mortalkin::Mob>,
+}
+
+// Mobs stand on the bottom of their tile like NPCs, drawn around the middle of
+// their frame.
+fn sprite_translation(point: Vec2, kind: MobKind) -> Vec3 {
+    let def = mob_def(kind);
+    let ground = point - Vec2::new(0., TILE_SIZE / 2.);
+    let center = ground + Vec2::new(0., def.frame_height / 2. * PIXEL_SCALE);
+    center.extend(super::y_sort(ground))
+}
+
+// Spawns the mobs we haven't seen yet in our zone, walks the others to their
+// new tile and drops the ones that left. Corpses are left alone until they
+// fade, and dead mobs we haven't seen aren't spawned at all.
+pub fn update(
+    mut commands: Commands,
+    game_state: Res<GameMap>,
+    current_char: Res<Character>,
+    sprites: Res<MobSprites>,
+    mut events: EventReader<mortalkin::Mob>,
+    mut mobs: ResMut<Mobs>,
+    mut query: Query<(
+        Entity,
+        &Mob,
+        &mut Position,
+        &mut Motion,
+        &mut Health,
+        Option<&Dead>,
+    )>,
+) {
+    mobs.received.extend(events.iter().cloned());
+    if !game_state.map_loaded {
+        return;
+    }
+
+    // Spawns only happen at the end of the frame, a second update for a new
+    // mob in the same frame must not spawn it again.
+    let mut spawned_ids = HashSet::new();
+    let received = std::mem::take(&mut mobs.received);
+    for update in received {
+        let kind = MobKind::from_i32(update.kind).unwrap_or(MobKind::SlimeGreen);
+        let (zone_id, position) = match update.position {
+            Some(position) => (
+                position.zone_id,
+                Position {
+                    x: position.x,
+                    y: position.y,
+                },
+            ),
+            None => continue,
+        };
+        let health = Health {
+            current: update.hp,
+            max: update.max_hp,
+        };
+
+        let mut known = false;
+        for (entity, mob, mut current, mut motion, mut current_health, dead) in query.iter_mut() {
+            if mob.id != update.id {
+                continue;
+            }
+
+            known = true;
+            if dead.is_some() {
+                continue;
+            }
+            if zone_id != current_char.zone_id {
+                commands.entity(entity).despawn();
+                continue;
+            }
+            if *current != position {
+                let distance = current.to_world().distance(position.to_world());
+                *current = position;
+                motion.start(position.to_world(), distance / TILE_SIZE / MOB_SPEED);
+            }
+            if *current_health != health {
+                *current_health = health;
+            }
+        }
+        // Mobs without a known max_hp send 0 for both and aren't dead.
+        let dead = update.max_hp > 0 && update.hp == 0;
+        if known || dead || zone_id != current_char.zone_id || !spawned_ids.insert(update.id) {
+            continue;
+        }
+
+        let point = position.to_world();
+        commands
+            .spawn_bundle(SpriteSheetBundle {
+                texture_atlas: sprites.atlases[&kind].clone(),
+                transform: Transform {
+                    translation: sprite_translation(point, kind),
+                    scale: Vec3::splat(PIXEL_SCALE),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .insert(Mob {
+                id: update.id,
+                kind,
+            })
+            .insert(MobAnimation {
+                frame: 0,
+                elapsed: 0.,
+                last_point: point,
+            })
+            .insert(position)
+            .insert(Motion::at(point))
+            .insert(health)
+            .insert(Blocking);
+    }
+}
+
+// Moves the sprites along with their motion and plays their sheet over and
+// over, facing the way they walk.
+pub fn animate(
+    time: Res<Time>,
+    mut query: Query<(
+        &Mob,
+        &Motion,
+        &mut MobAnimation,
+        &mut Transform,
+        &mut TextureAtlasSprite,
+    )>,
+) {
+    for (mob, motion, mut animation, mut transform, mut sprite) in query.iter_mut() {
+        let point = motion.current();
+        transform.translation = sprite_translation(point, mob.kind);
+
+        let delta = point.x - animation.last_point.x;
+        if delta < 0. {
+            sprite.flip_x = true;
+        } else if delta > 0. {
+            sprite.flip_x = false;
+        }
+        animation.last_point = point;
+
+        let def = mob_def(mob.kind);
+        animation.elapsed += time.delta_seconds();
+        while animation.elapsed >= 1. / def.fps {
+            animation.elapsed -= 1. / def.fps;
+            animation.frame = (animation.frame + 1) % def.frame_count;
+        }
+        sprite.index = animation.frame as u32;
+    }
+}
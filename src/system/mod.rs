@@ -1,16 +1,47 @@
+use std::collections::HashSet;
 use std::sync::Mutex;
 
-use crate::pursuit::api::mortalkin::{GameNotif, PlayGamePayload};
+use crate::pursuit::api::mortalkin::{self, GameNotif, PlayGamePayload};
 
-use bevy::prelude::*;
-use bevy::render::camera::Camera;
-
-use bevy_tilemap::prelude::*;
+use bevy::{ecs::system::SystemParam, prelude::*};
 
+pub mod animation;
+pub mod camera;
 pub mod char_creation;
 pub mod char_selection;
+pub mod chat;
+pub mod collision;
+pub mod combat;
+pub mod death;
+pub mod dialogue;
+#[cfg(feature = "editor")]
+pub mod editor;
+pub mod emote;
 pub mod field;
+pub mod fog;
+pub mod interaction;
+pub mod lighting;
 pub mod login;
+pub mod map;
+pub mod map_cache;
+pub mod minimap;
+pub mod mob;
+pub mod movement;
+pub mod nameplate;
+pub mod npc;
+pub mod particles;
+pub mod pathfinding;
+pub mod picking;
+pub mod props;
+pub mod target;
+pub mod terrain;
+pub mod weather;
+pub mod zone;
+
+pub const TILE_SIZE: f32 = 32.;
+// The art is drawn for 16px tiles, everything is scaled up to TILE_SIZE.
+pub const PIXEL_SCALE: f32 = TILE_SIZE / 16.;
+pub const Y_SORT_Z: f32 = 100.;
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub enum AppState {
@@ -18,6 +49,8 @@ pub enum AppState {
     CharSelectionMenu,
     CharCreationMenu,
     Field,
+    #[cfg(feature = "editor")]
+    Editor,
 }
 
 pub struct RequestSender {
@@ -41,6 +74,7 @@ pub struct Character {
     pub id: u32,
     pub name: String,
     pub position: Option<Position>,
+    pub zone_id: u32,
 }
 
 #[derive(Default, Copy, Clone, PartialEq)]
@@ -49,6 +83,19 @@ pub struct Position {
     pub y: i32,
 }
 
+impl Position {
+    pub fn to_world(&self) -> Vec2 {
+        Vec2::new(self.x as f32 * TILE_SIZE, self.y as f32 * TILE_SIZE)
+    }
+
+    pub fn from_world(point: Vec2) -> Self {
+        Self {
+            x: (point.x / TILE_SIZE).round() as i32,
+            y: (point.y / TILE_SIZE).round() as i32,
+        }
+    }
+}
+
 pub struct ButtonMaterials {
     normal: Handle<ColorMaterial>,
     hovered: Handle<ColorMaterial>,
@@ -73,25 +120,31 @@ pub fn setup_camera(mut commands: Commands) {
 #[derive(Default, Clone)]
 pub struct TileSpriteHandles {
     handles: Vec<HandleUntyped>,
+    atlas: Handle<TextureAtlas>,
     atlas_loaded: bool,
 }
 
 #[derive(Default, Clone)]
 pub struct GameMap {
+    map_requested: bool,
+    tilemap_spawned: bool,
     map_loaded: bool,
+    // Tile the local player appears on once the map is built, when coming
+    // through a portal.
+    arrival: Option<Position>,
+    data: map::MapData,
+    collision: collision::CollisionMap,
 }
 
 impl GameMap {
-    fn try_move_player(
-        &mut self,
-        position: &mut Position,
-        camera_translation: &mut Vec3,
-        delta_xy: (i32, i32),
-    ) {
+    fn try_move_player(&mut self, position: &mut Position, delta_xy: (i32, i32)) -> bool {
+        if !self.collision.can_move(*position, delta_xy) {
+            return false;
+        }
+
         position.x = position.x + delta_xy.0;
         position.y = position.y + delta_xy.1;
-        camera_translation.x = camera_translation.x + (delta_xy.0 as f32 * 32.);
-        camera_translation.y = camera_translation.y + (delta_xy.1 as f32 * 32.);
+        true
     }
 }
 
@@ -105,173 +158,247 @@ pub fn setup_tile(
 #[derive(Default)]
 pub struct Player {
     id: u32,
+    name: String,
+}
+
+// Party the player is in, 0 for none.
+#[derive(Default, Clone, Copy, PartialEq)]
+pub struct Party(pub u32);
+
+// Only known for players the server sends hit points for.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Health {
+    pub current: u32,
+    pub max: u32,
 }
 
 #[derive(Bundle)]
 pub struct PlayerBundle {
     player: Player,
+    party: Party,
     position: Position,
-    render: Render,
+    speed: movement::MovementSpeed,
+    motion: movement::Motion,
+    animation: animation::AnimationState,
+    #[bundle]
+    sprite: SpriteSheetBundle,
 }
 
-#[derive(Default)]
-pub struct Render {
-    sprite_index: usize,
-    sprite_order: usize,
+impl PlayerBundle {
+    pub fn new(
+        id: u32,
+        name: String,
+        position: Position,
+        animations: &animation::CharacterAnimations,
+    ) -> Self {
+        let point = position.to_world();
+        Self {
+            player: Player { id, name },
+            party: Party::default(),
+            position,
+            speed: Default::default(),
+            motion: movement::Motion::at(point),
+            animation: animation::AnimationState::new(point),
+            sprite: SpriteSheetBundle {
+                texture_atlas: animations.atlas.clone(),
+                sprite: TextureAtlasSprite::new(animations.first_frame(animation::Clip::Idle)),
+                transform: Transform {
+                    translation: point.extend(y_sort(point)),
+                    scale: Vec3::splat(animation::CHARACTER_SCALE),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        }
+    }
 }
 
-fn move_sprite(
-    map: &mut Tilemap,
-    previous_position: Position,
-    position: Position,
-    render: &Render,
-) {
-    // We need to first remove where we were prior.
-    map.clear_tile((previous_position.x, previous_position.y), 1)
-        .unwrap();
-    // We then need to update where we are going!
-    let tile = Tile {
-        point: (position.x, position.y),
-        sprite_index: render.sprite_index,
-        sprite_order: render.sprite_order,
-        ..Default::default()
-    };
-    map.insert_tile(tile).unwrap();
+// Characters and props share one z range, ordered by how far down the screen
+// they stand so whoever is lower is drawn in front.
+pub fn y_sort(point: Vec2) -> f32 {
+    Y_SORT_Z - point.y * 0.001
 }
 
-pub fn character_movement(
-    current_char: Res<Character>,
-    mut game_state: ResMut<GameMap>,
-    keyboard_input: Res<Input<KeyCode>>,
-    time: Res<Time>,
-    mut map_query: Query<(&mut Tilemap, &mut Timer)>,
-    mut player_query: Query<(&mut Position, &Render, &Player)>,
-    mut camera_query: Query<(&Camera, &mut Transform)>,
-) {
-    if !game_state.map_loaded {
-        return;
+pub fn place_sprites(mut query: Query<(&movement::Motion, &mut Transform), With<Player>>) {
+    for (motion, mut transform) in query.iter_mut() {
+        let point = motion.current();
+        transform.translation = point.extend(y_sort(point));
     }
+}
 
-    for (mut map, mut timer) in map_query.iter_mut() {
-        timer.tick(time.delta());
-        if !timer.finished() {
-            continue;
-        }
-
-        for (mut position, render, player) in player_query.iter_mut() {
-            if player.id != current_char.id {
-                move_sprite(&mut map, *position, *position, render);
-                continue
-            }
-
-            for key in keyboard_input.get_pressed() {
-                for (_camera, mut camera_transform) in camera_query.iter_mut() {
-                    // First we need to store our very current position.
-                    let previous_position = *position;
-
-                    // Of course we need to control where we are going to move our
-                    // dwarf friend.
-                    use KeyCode::*;
-                    match key {
-                        W | Numpad8 | Up | K => {
-                            game_state.try_move_player(
-                                &mut position,
-                                &mut camera_transform.translation,
-                                (0, 1),
-                            );
-                        }
-                        A | Numpad4 | Left | H => {
-                            game_state.try_move_player(
-                                &mut position,
-                                &mut camera_transform.translation,
-                                (-1, 0),
-                            );
-                        }
-                        S | Numpad2 | Down | J => {
-                            game_state.try_move_player(
-                                &mut position,
-                                &mut camera_transform.translation,
-                                (0, -1),
-                            );
-                        }
-                        D | Numpad6 | Right | L => {
-                            game_state.try_move_player(
-                                &mut position,
-                                &mut camera_transform.translation,
-                                (1, 0),
-                            );
-                        }
-
-                        Numpad9 | U => game_state.try_move_player(
-                            &mut position,
-                            &mut camera_transform.translation,
-                            (1, 1),
-                        ),
-                        Numpad3 | M => game_state.try_move_player(
-                            &mut position,
-                            &mut camera_transform.translation,
-                            (1, -1),
-                        ),
-                        Numpad1 | N => game_state.try_move_player(
-                            &mut position,
-                            &mut camera_transform.translation,
-                            (-1, -1),
-                        ),
-                        Numpad7 | Y => game_state.try_move_player(
-                            &mut position,
-                            &mut camera_transform.translation,
-                            (-1, 1),
-                        ),
-
-                        _ => {}
-                    }
+// Every part of a notification goes to the feature it's for as an event, a
+// new kind of update gets a writer here.
+#[derive(SystemParam)]
+pub struct NotifEvents<'a> {
+    characters: EventWriter<'a, mortalkin::Character>,
+    server_time: EventWriter<'a, lighting::ServerTime>,
+    weather: EventWriter<'a, mortalkin::Weather>,
+    chat: EventWriter<'a, mortalkin::ChatMessage>,
+    emotes: EventWriter<'a, mortalkin::Emote>,
+    interactions: EventWriter<'a, mortalkin::InteractionResult>,
+    mobs: EventWriter<'a, mortalkin::Mob>,
+    damage: EventWriter<'a, mortalkin::Damage>,
+    deaths: EventWriter<'a, mortalkin::Death>,
+}
 
-                    // Finally now we will move the sprite! ... Provided he had
-                    // moved!
-                    move_sprite(&mut map, previous_position, *position, render);
-                }
-            }
+impl<'a> NotifEvents<'a> {
+    fn route(&mut self, notif: GameNotif, current_char: &Character) {
+        if notif.server_time != 0 {
+            self.server_time
+                .send(lighting::ServerTime(notif.server_time));
         }
+        // Weather comes along with the notification that moves us to another
+        // zone, so it's for the zone we're in according to it.
+        let our_zone = notif
+            .characters
+            .iter()
+            .find(|chars| chars.id == current_char.id)
+            .and_then(|chars| chars.position.as_ref())
+            .map_or(current_char.zone_id, |position| position.zone_id);
+        self.weather.send_batch(
+            notif
+                .weather
+                .into_iter()
+                .filter(|zone_weather| zone_weather.zone_id == our_zone),
+        );
+
+        self.characters.send_batch(notif.characters.into_iter());
+        self.chat.send_batch(notif.chat.into_iter());
+        self.emotes.send_batch(notif.emotes.into_iter());
+        self.interactions.send_batch(notif.interactions.into_iter());
+        self.mobs.send_batch(notif.mobs.into_iter());
+        self.damage.send_batch(notif.damage.into_iter());
+        self.deaths.send_batch(notif.deaths.into_iter());
     }
 }
 
+// Hands out everything the server sent since the last frame.
 pub fn incoming_notif(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
     response_receiver: Res<ResponseReceiver>,
-    texture_atlases: Res<Assets<TextureAtlas>>,
+    animations: Res<animation::CharacterAnimations>,
     current_char: Res<Character>,
-    mut query: Query<&mut Tilemap>,
+    mut events: NotifEvents,
 ) {
-    let resp = response_receiver.rx.lock().unwrap().try_next();
-    match resp {
-        Ok(Some(conn_resp)) => {
-            for chars in conn_resp.characters.into_iter() {
-                if chars.id == current_char.id {
-                    continue;
+    // Players can't be spawned before their animations are there, the
+    // notifications wait in the channel until then.
+    if !animations.loaded {
+        return;
+    }
+
+    let mut rx = response_receiver.rx.lock().unwrap();
+    while let Ok(Some(notif)) = rx.try_next() {
+        events.route(notif, &current_char);
+    }
+}
+
+pub fn update_players(
+    mut commands: Commands,
+    mut events: EventReader<mortalkin::Character>,
+    animations: Res<animation::CharacterAnimations>,
+    current_char: Res<Character>,
+    mut transition: ResMut<zone::ZoneTransition>,
+    mut player_query: Query<(
+        Entity,
+        &Player,
+        &mut Position,
+        &mut movement::Motion,
+        &movement::MovementSpeed,
+        &mut Party,
+        Option<&mut Health>,
+    )>,
+) {
+    // Spawns only happen at the end of the frame, a second update for someone
+    // new in the same frame must not spawn them again.
+    let mut spawned_ids = HashSet::new();
+    for chars in events.iter() {
+        let party = Party(chars.party_id);
+        let health = Health {
+            current: chars.hp,
+            max: chars.max_hp,
+        };
+        for (entity, player, _, _, _, mut current_party, current_health) in player_query.iter_mut()
+        {
+            if player.id != chars.id {
+                continue;
+            }
+            if *current_party != party {
+                *current_party = party;
+            }
+            match current_health {
+                Some(mut current_health) if health.max != 0 => {
+                    if *current_health != health {
+                        *current_health = health;
+                    }
+                }
+                None if health.max != 0 => {
+                    commands.entity(entity).insert(health);
                 }
+                _ => {}
+            }
+        }
+
+        // Without a position there's no telling where they are, they stay
+        // where we last saw them.
+        let (position, zone_id) = match &chars.position {
+            Some(position) => (
+                Position {
+                    x: position.x,
+                    y: position.y,
+                },
+                position.zone_id,
+            ),
+            None => continue,
+        };
+
+        if chars.id == current_char.id {
+            // The server moved us to another zone, e.g. through a portal.
+            if zone_id != current_char.zone_id {
+                transition.start(zone_id, position);
+            }
+            continue;
+        }
 
-                for map in query.iter_mut() {
-                    let texture_atlas = texture_atlases.get(map.texture_atlas()).unwrap();
-                    let dwarf_sprite: Handle<Texture> =
-                        asset_server.get_handle("texture/sprite/sensei.png");
-                    let dwarf_sprite_index =
-                        texture_atlas.get_texture_index(&dwarf_sprite).unwrap();
-
-                    commands.spawn().insert_bundle(PlayerBundle {
-                        player: Player {
-                            id: chars.id,
-                        },
-                        position: Position { x: 2, y: 2 },
-                        render: Render {
-                            sprite_index: dwarf_sprite_index,
-                            sprite_order: 1,
-                        },
-                    });
+        // Only players in our zone are on the field, the ones who just left
+        // it go away.
+        if zone_id != current_char.zone_id {
+            for (entity, player, ..) in player_query.iter_mut() {
+                if player.id == chars.id {
+                    commands.entity(entity).despawn();
                 }
             }
+            continue;
+        }
+
+        // Players we already know about walk over to their new tile.
+        let mut known = false;
+        for (_, player, mut current, mut motion, speed, ..) in player_query.iter_mut() {
+            if player.id != chars.id {
+                continue;
+            }
+
+            known = true;
+            if *current != position {
+                let distance = current.to_world().distance(position.to_world());
+                *current = position;
+                motion.start(position.to_world(), distance / TILE_SIZE / speed.0);
+            }
+        }
+        if known || !spawned_ids.insert(chars.id) {
+            continue;
+        }
+
+        let mut spawned = commands.spawn();
+        spawned
+            .insert_bundle(PlayerBundle::new(
+                chars.id,
+                chars.name.clone(),
+                position,
+                &animations,
+            ))
+            .insert(party)
+            .insert(collision::Blocking);
+        if health.max != 0 {
+            spawned.insert(health);
         }
-        Ok(None) => {}
-        Err(_) => {}
     }
 }
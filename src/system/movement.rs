@@ -0,0 +1,264 @@
+use super::chat::Chat;
+use super::death::Respawn;
+use super::dialogue::Dialogue;
+use super::Character;
+use super::GameMap;
+use super::Player;
+use super::Position;
+use super::RequestSender;
+use super::Token;
+use super::TILE_SIZE;
+use crate::pursuit::api::mortalkin;
+
+use bevy::prelude::*;
+
+// Tiles per second.
+pub const DEFAULT_SPEED: f32 = 6.;
+
+pub enum MovementMode {
+    // Walk from tile centre to tile centre, one step at a time.
+    Tile,
+    // Walk freely in pixels, the tile position follows the sprite.
+    Free,
+}
+
+impl Default for MovementMode {
+    fn default() -> Self {
+        MovementMode::Tile
+    }
+}
+
+// T switches between the two, free movement ends back on a tile centre.
+pub fn toggle_mode(
+    current_char: Res<Character>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut chat: ResMut<Chat>,
+    dialogue: Res<Dialogue>,
+    mut mode: ResMut<MovementMode>,
+    mut query: Query<(&Player, &Position, &mut Motion, &MovementSpeed)>,
+) {
+    if chat.typing || dialogue.is_open() || !keyboard_input.just_pressed(KeyCode::T) {
+        return;
+    }
+
+    *mode = match *mode {
+        MovementMode::Tile => {
+            chat.system("Free movement.".to_string());
+            MovementMode::Free
+        }
+        MovementMode::Free => {
+            for (player, position, mut motion, speed) in query.iter_mut() {
+                if player.id != current_char.id {
+                    continue;
+                }
+
+                let point = position.to_world();
+                let distance = motion.current().distance(point) / TILE_SIZE;
+                motion.start(point, distance / speed.0);
+            }
+            chat.system("Tile movement.".to_string());
+            MovementMode::Tile
+        }
+    };
+}
+
+pub struct MovementSpeed(pub f32);
+
+impl Default for MovementSpeed {
+    fn default() -> Self {
+        MovementSpeed(DEFAULT_SPEED)
+    }
+}
+
+// Where the local player wants to go this frame, combined from every held key.
+#[derive(Default)]
+pub struct MoveIntent {
+    pub direction: (i32, i32),
+}
+
+// Pixel position of a character, tweened between two tile centres.
+#[derive(Default)]
+pub struct Motion {
+    from: Vec2,
+    to: Vec2,
+    elapsed: f32,
+    duration: f32,
+}
+
+impl Motion {
+    pub fn at(point: Vec2) -> Self {
+        Self {
+            from: point,
+            to: point,
+            elapsed: 0.,
+            duration: 0.,
+        }
+    }
+
+    pub fn start(&mut self, to: Vec2, duration: f32) {
+        // Time past the end of a tween that just finished goes into the next
+        // one, so walking over several tiles doesn't stutter at each centre.
+        let carried = (self.elapsed - self.duration).max(0.);
+        self.from = self.current();
+        self.to = to;
+        self.elapsed = carried.min(duration);
+        self.duration = duration;
+    }
+
+    pub fn is_moving(&self) -> bool {
+        self.elapsed < self.duration
+    }
+
+    pub fn current(&self) -> Vec2 {
+        if !self.is_moving() {
+            return self.to;
+        }
+
+        self.from.lerp(self.to, self.elapsed / self.duration)
+    }
+}
+
+pub fn read_input(
+    current_char: Res<Character>,
+    keyboard_input: Res<Input<KeyCode>>,
+    chat: Res<Chat>,
+    dialogue: Res<Dialogue>,
+    respawn: Res<Respawn>,
+    mut query: Query<(&Player, &mut MoveIntent)>,
+) {
+    let mut direction = (0, 0);
+    // Letters typed into the chat don't walk, and neither do the keys picking
+    // a dialogue choice. The dead don't walk at all.
+    let pressed = keyboard_input
+        .get_pressed()
+        .filter(|_| !chat.typing && !dialogue.is_open() && !respawn.is_dead());
+    for key in pressed {
+        use KeyCode::*;
+        let delta = match key {
+            W | Numpad8 | Up | K => (0, 1),
+            A | Numpad4 | Left | H => (-1, 0),
+            S | Numpad2 | Down | J => (0, -1),
+            D | Numpad6 | Right | L => (1, 0),
+
+            Numpad9 | U => (1, 1),
+            Numpad3 | M => (1, -1),
+            Numpad1 | N => (-1, -1),
+            Numpad7 | Y => (-1, 1),
+
+            _ => (0, 0),
+        };
+        direction.0 += delta.0;
+        direction.1 += delta.1;
+    }
+
+    // Opposite keys cancel out, and W+D is a single diagonal step.
+    direction.0 = direction.0.signum();
+    direction.1 = direction.1.signum();
+
+    for (player, mut intent) in query.iter_mut() {
+        if player.id == current_char.id {
+            intent.direction = direction;
+        }
+    }
+}
+
+pub fn step(
+    current_char: Res<Character>,
+    mut game_state: ResMut<GameMap>,
+    mode: Res<MovementMode>,
+    time: Res<Time>,
+    request_sender: Res<RequestSender>,
+    token: Res<Token>,
+    mut player_query: Query<(
+        &mut Position,
+        &mut Motion,
+        &MoveIntent,
+        &MovementSpeed,
+        &Player,
+    )>,
+) {
+    if !game_state.map_loaded {
+        return;
+    }
+
+    for (mut position, mut motion, intent, speed, player) in player_query.iter_mut() {
+        if player.id != current_char.id {
+            continue;
+        }
+
+        let previous_position = *position;
+        match *mode {
+            MovementMode::Tile => {
+                if motion.is_moving() || intent.direction == (0, 0) {
+                    continue;
+                }
+
+                if !game_state.try_move_player(&mut position, intent.direction) {
+                    continue;
+                }
+
+                let distance = Vec2::new(intent.direction.0 as f32, intent.direction.1 as f32);
+                motion.start(position.to_world(), distance.length() / speed.0);
+            }
+            MovementMode::Free => {
+                let direction = Vec2::new(intent.direction.0 as f32, intent.direction.1 as f32)
+                    .normalize_or_zero();
+                // A long frame doesn't get to skip over a tile, every tile
+                // we cross is checked.
+                let delta = (direction * speed.0 * TILE_SIZE * time.delta_seconds())
+                    .clamp_length_max(TILE_SIZE);
+
+                // Each axis is tried on its own so we slide along walls
+                // instead of sticking to them.
+                let mut point = motion.current();
+                for axis_delta in [Vec2::new(delta.x, 0.), Vec2::new(0., delta.y)].iter() {
+                    let next = point + *axis_delta;
+                    let next_position = Position::from_world(next);
+                    if next_position != *position {
+                        let delta_xy = (next_position.x - position.x, next_position.y - position.y);
+                        if !game_state.try_move_player(&mut position, delta_xy) {
+                            continue;
+                        }
+                    }
+                    point = next;
+                }
+                *motion = Motion::at(point);
+
+                if *position == previous_position {
+                    continue;
+                }
+            }
+        }
+
+        request_sender
+            .tx
+            .lock()
+            .unwrap()
+            .unbounded_send(mortalkin::PlayGamePayload {
+                token: token.token.clone(),
+                character_id: player.id,
+                position: Some(mortalkin::Position {
+                    x: position.x,
+                    y: position.y,
+                    zone_id: current_char.zone_id,
+                }),
+                enter_portal: None,
+                chat: None,
+                emote: None,
+                interact: None,
+                attack: None,
+            })
+            .unwrap();
+    }
+}
+
+pub fn advance(time: Res<Time>, mut query: Query<&mut Motion>) {
+    for mut motion in query.iter_mut() {
+        if motion.is_moving() {
+            motion.elapsed += time.delta_seconds();
+        } else if motion.elapsed > motion.duration {
+            // Only a tween started in the same frame gets the leftover time.
+            motion.elapsed = motion.duration;
+        }
+    }
+}
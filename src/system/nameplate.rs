@@ -0,0 +1,233 @@
+use std::cmp::Ordering;
+use std::collections::HashSet;
+
+use super::chat::Chat;
+use super::Character;
+use super::Health;
+use super::Party;
+use super::Player;
+use super::TILE_SIZE;
+
+use bevy::{prelude::*, text::Text2dSize};
+
+// Over the weather, under the fog so unexplored players stay hidden.
+const NAMEPLATE_Z: f32 = 190.;
+const FONT_SIZE: f32 = 14.;
+// Above the middle of the player's tile.
+const NAME_OFFSET: f32 = TILE_SIZE * 1.1;
+const BAR_OFFSET: f32 = TILE_SIZE * 0.8;
+const BAR_WIDTH: f32 = TILE_SIZE;
+const BAR_HEIGHT: f32 = 4.;
+// Labels of players further away than this from ours are hidden.
+const MAX_DISTANCE: f32 = TILE_SIZE * 12.;
+
+const SELF_COLOUR: Color = Color::rgb(1., 0.85, 0.3);
+const PARTY_COLOUR: Color = Color::rgb(0.45, 0.9, 0.45);
+const OTHER_COLOUR: Color = Color::WHITE;
+
+pub struct Nameplates {
+    // Hit point bars under the names, toggled with V.
+    pub show_health: bool,
+    font: Handle<Font>,
+    bar_back: Handle<ColorMaterial>,
+    bar_fill: Handle<ColorMaterial>,
+}
+
+impl FromWorld for Nameplates {
+    fn from_world(world: &mut World) -> Self {
+        let font = {
+            let asset_server = world.get_resource::<AssetServer>().unwrap();
+            asset_server.load("fonts/FiraSans-Bold.otf")
+        };
+        let mut materials = world.get_resource_mut::<Assets<ColorMaterial>>().unwrap();
+        Self {
+            show_health: true,
+            font,
+            bar_back: materials.add(Color::rgba(0., 0., 0., 0.6).into()),
+            bar_fill: materials.add(Color::rgb(0.85, 0.2, 0.2).into()),
+        }
+    }
+}
+
+// The label of `player`, kept as a separate entity so it isn't scaled down
+// with the character sprite.
+pub struct Nameplate {
+    player: Entity,
+}
+
+pub struct HealthBar {
+    player: Entity,
+    // The red part over the dark background.
+    fill: bool,
+}
+
+pub fn spawn(
+    mut commands: Commands,
+    nameplates: Res<Nameplates>,
+    query: Query<(Entity, &Player), Added<Player>>,
+) {
+    for (entity, player) in query.iter() {
+        commands
+            .spawn_bundle(Text2dBundle {
+                text: Text::with_section(
+                    player.name.clone(),
+                    TextStyle {
+                        font: nameplates.font.clone(),
+                        font_size: FONT_SIZE,
+                        color: OTHER_COLOUR,
+                    },
+                    TextAlignment {
+                        vertical: VerticalAlign::Center,
+                        horizontal: HorizontalAlign::Center,
+                    },
+                ),
+                ..Default::default()
+            })
+            .insert(Nameplate { player: entity });
+
+        for (fill, material) in [(false, &nameplates.bar_back), (true, &nameplates.bar_fill)].iter()
+        {
+            commands
+                .spawn_bundle(SpriteBundle {
+                    sprite: Sprite::new(Vec2::new(BAR_WIDTH, BAR_HEIGHT)),
+                    material: (*material).clone(),
+                    visible: Visible {
+                        is_visible: false,
+                        is_transparent: true,
+                    },
+                    ..Default::default()
+                })
+                .insert(HealthBar {
+                    player: entity,
+                    fill: *fill,
+                });
+        }
+    }
+}
+
+pub fn toggle_health(
+    keyboard_input: Res<Input<KeyCode>>,
+    chat: Res<Chat>,
+    mut nameplates: ResMut<Nameplates>,
+) {
+    if !chat.typing && keyboard_input.just_pressed(KeyCode::V) {
+        nameplates.show_health = !nameplates.show_health;
+    }
+}
+
+fn overlaps(a: (Vec2, Vec2), b: (Vec2, Vec2)) -> bool {
+    (a.0.x - b.0.x).abs() * 2. < a.1.x + b.1.x && (a.0.y - b.0.y).abs() * 2. < a.1.y + b.1.y
+}
+
+// Labels follow their players. The nearest ones to ours win when they'd
+// cover each other, and far away ones aren't shown at all.
+pub fn update(
+    mut commands: Commands,
+    current_char: Res<Character>,
+    nameplates: Res<Nameplates>,
+    player_query: Query<(&Player, &Transform, &Party, Option<&Health>)>,
+    mut label_query: Query<
+        (
+            Entity,
+            &Nameplate,
+            &Text2dSize,
+            &mut Text,
+            &mut Transform,
+            &mut Visible,
+        ),
+        Without<Player>,
+    >,
+    mut bar_query: Query<
+        (
+            Entity,
+            &HealthBar,
+            &mut Sprite,
+            &mut Transform,
+            &mut Visible,
+        ),
+        (Without<Player>, Without<Nameplate>),
+    >,
+) {
+    let ours = player_query
+        .iter()
+        .find(|(player, ..)| player.id == current_char.id)
+        .map(|(_, transform, party, _)| (transform.translation.truncate(), *party));
+
+    let mut labels = Vec::new();
+    for (entity, nameplate, size, mut text, mut transform, mut visible) in label_query.iter_mut() {
+        let (player, player_transform, party, _) = match player_query.get(nameplate.player) {
+            Ok(player) => player,
+            Err(_) => {
+                commands.entity(entity).despawn();
+                continue;
+            }
+        };
+
+        let point = player_transform.translation.truncate();
+        transform.translation = (point + Vec2::new(0., NAME_OFFSET)).extend(NAMEPLATE_Z);
+
+        let colour = match ours {
+            _ if player.id == current_char.id => SELF_COLOUR,
+            Some((_, our_party)) if our_party.0 != 0 && our_party == *party => PARTY_COLOUR,
+            _ => OTHER_COLOUR,
+        };
+        if text.sections[0].style.color != colour {
+            text.sections[0].style.color = colour;
+        }
+
+        let distance = ours.map_or(0., |(our_point, _)| our_point.distance(point));
+        visible.is_visible = distance <= MAX_DISTANCE;
+        if visible.is_visible {
+            let rect = (
+                transform.translation.truncate(),
+                Vec2::new(size.size.width, size.size.height),
+            );
+            labels.push((entity, nameplate.player, distance, rect));
+        }
+    }
+
+    labels.sort_by(|a, b| a.2.partial_cmp(&b.2).unwrap_or(Ordering::Equal));
+    let mut shown: Vec<(Vec2, Vec2)> = Vec::new();
+    let mut shown_players = HashSet::new();
+    for (entity, player, _, rect) in labels {
+        if shown.iter().any(|other| overlaps(*other, rect)) {
+            if let Ok((.., mut visible)) = label_query.get_mut(entity) {
+                visible.is_visible = false;
+            }
+            continue;
+        }
+        shown.push(rect);
+        shown_players.insert(player);
+    }
+
+    for (entity, bar, mut sprite, mut transform, mut visible) in bar_query.iter_mut() {
+        let health = match player_query.get(bar.player) {
+            Ok((_, player_transform, _, health)) => {
+                let point = player_transform.translation.truncate();
+                transform.translation = (point + Vec2::new(0., BAR_OFFSET))
+                    .extend(NAMEPLATE_Z + if bar.fill { 0.2 } else { 0.1 });
+                health
+            }
+            Err(_) => {
+                commands.entity(entity).despawn();
+                continue;
+            }
+        };
+        // Bars go with the label, hidden or not.
+        let label_shown = shown_players.contains(&bar.player);
+        let health = match health {
+            Some(health) if nameplates.show_health && label_shown && health.max > 0 => health,
+            _ => {
+                visible.is_visible = false;
+                continue;
+            }
+        };
+        visible.is_visible = true;
+        if bar.fill {
+            // Shrinks towards the left edge.
+            let ratio = (health.current as f32 / health.max as f32).min(1.);
+            sprite.size.x = BAR_WIDTH * ratio;
+            transform.translation.x -= BAR_WIDTH * (1. - ratio) / 2.;
+        }
+    }
+}
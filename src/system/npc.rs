@@ -0,0 +1,67 @@
+use super::collision::Blocking;
+use super::interaction::Interactable;
+use super::map::{InteractionKind, MapData};
+use super::Position;
+use super::PIXEL_SCALE;
+use super::TILE_SIZE;
+
+use bevy::prelude::*;
+
+pub struct Npc {
+    pub name: String,
+    pub dialogue: Option<String>,
+}
+
+// NPCs stand on their tile like props do, they're in the same atlas. Like
+// players, they only keep others off the tile they stand on.
+pub fn spawn_npcs(
+    commands: &mut Commands,
+    data: &MapData,
+    texture_atlas_handle: &Handle<TextureAtlas>,
+    texture_atlas: &TextureAtlas,
+    asset_server: &AssetServer,
+) {
+    for placed in data.npcs.iter() {
+        let handle: Handle<Texture> = asset_server.get_handle(placed.texture.as_str());
+        // Downloaded maps can name textures this client doesn't have.
+        let sprite_index = match texture_atlas.get_texture_index(&handle) {
+            Some(sprite_index) => sprite_index,
+            None => {
+                warn!(
+                    "Skipping NPC {}, no texture {}",
+                    placed.name, placed.texture
+                );
+                continue;
+            }
+        };
+        let rect = texture_atlas.textures[sprite_index];
+        let position = Position {
+            x: placed.x,
+            y: placed.y,
+        };
+        let ground = position.to_world() - Vec2::new(0., TILE_SIZE / 2.);
+        let center = ground + Vec2::new(0., rect.height() / 2. * PIXEL_SCALE);
+
+        commands
+            .spawn_bundle(SpriteSheetBundle {
+                texture_atlas: texture_atlas_handle.clone(),
+                sprite: TextureAtlasSprite::new(sprite_index as u32),
+                transform: Transform {
+                    translation: center.extend(super::y_sort(ground)),
+                    scale: Vec3::splat(PIXEL_SCALE),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .insert(Npc {
+                name: placed.name.clone(),
+                dialogue: placed.dialogue.clone(),
+            })
+            .insert(Interactable {
+                kind: InteractionKind::Talk,
+                name: placed.name.clone(),
+            })
+            .insert(position)
+            .insert(Blocking);
+    }
+}
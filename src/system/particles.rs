@@ -0,0 +1,176 @@
+use bevy::prelude::*;
+
+// Spawns sprites that fly off in a direction and disappear after a while.
+// Expired particles are hidden and kept for the next ones, so an emitter
+// never has more entities than `max_particles`.
+pub struct ParticleEmitter {
+    // Particles per second, 0 for one that only bursts.
+    pub rate: f32,
+    // Seconds a particle lives.
+    pub lifetime: f32,
+    pub velocity: Vec2,
+    // Each particle's velocity is off by up to this much on either axis.
+    pub velocity_spread: Vec2,
+    // Half the size of the box around the emitter particles appear in.
+    pub area: Vec2,
+    pub size: Vec2,
+    pub material: Handle<ColorMaterial>,
+    // Particles get smaller until they're gone at the end of their life.
+    pub shrink: bool,
+    pub max_particles: usize,
+    owed: f32,
+    burst: usize,
+    seed: u64,
+    alive: usize,
+    pool: Vec<Entity>,
+}
+
+impl ParticleEmitter {
+    pub fn new(material: Handle<ColorMaterial>, size: Vec2, seed: u64) -> Self {
+        Self {
+            rate: 0.,
+            lifetime: 1.,
+            velocity: Vec2::ZERO,
+            velocity_spread: Vec2::ZERO,
+            area: Vec2::ZERO,
+            size,
+            material,
+            shrink: false,
+            max_particles: 64,
+            owed: 0.,
+            burst: 0,
+            // Xorshift gets stuck on 0.
+            seed: seed.max(1),
+            alive: 0,
+            pool: Vec::new(),
+        }
+    }
+
+    // Emits `count` particles at once on the next update, e.g. for a hit.
+    pub fn burst(&mut self, count: usize) {
+        self.burst += count;
+    }
+
+    // Between -1 and 1.
+    fn random(&mut self) -> f32 {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 7;
+        self.seed ^= self.seed << 17;
+        (self.seed >> 40) as f32 / (1u64 << 23) as f32 - 1.
+    }
+
+    fn spread(&mut self, half: Vec2) -> Vec2 {
+        Vec2::new(self.random() * half.x, self.random() * half.y)
+    }
+}
+
+pub struct Particle {
+    emitter: Entity,
+    alive: bool,
+    velocity: Vec2,
+    age: f32,
+    lifetime: f32,
+    size: Vec2,
+    shrink: bool,
+}
+
+pub fn emit(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut emitter_query: Query<(Entity, &mut ParticleEmitter, &GlobalTransform)>,
+    mut particle_query: Query<(&mut Particle, &mut Transform, &mut Sprite, &mut Visible)>,
+) {
+    for (entity, mut emitter, global_transform) in emitter_query.iter_mut() {
+        emitter.owed += emitter.rate * time.delta_seconds();
+        let mut count = emitter.owed as usize + emitter.burst;
+        emitter.owed = emitter.owed.fract();
+        emitter.burst = 0;
+        count = count.min(emitter.max_particles.saturating_sub(emitter.alive));
+
+        for _ in 0..count {
+            let area = emitter.area;
+            let spread = emitter.velocity_spread;
+            let offset = emitter.spread(area);
+            let velocity = emitter.velocity + emitter.spread(spread);
+            let translation = global_transform.translation + offset.extend(0.);
+            let particle = Particle {
+                emitter: entity,
+                alive: true,
+                velocity,
+                age: 0.,
+                lifetime: emitter.lifetime,
+                size: emitter.size,
+                shrink: emitter.shrink,
+            };
+            emitter.alive += 1;
+
+            let reused = emitter
+                .pool
+                .pop()
+                .and_then(|pooled| particle_query.get_mut(pooled).ok());
+            match reused {
+                Some((mut pooled, mut transform, mut sprite, mut visible)) => {
+                    transform.translation = translation;
+                    sprite.size = emitter.size;
+                    visible.is_visible = true;
+                    *pooled = particle;
+                }
+                None => {
+                    commands
+                        .spawn_bundle(SpriteBundle {
+                            sprite: Sprite::new(emitter.size),
+                            material: emitter.material.clone(),
+                            transform: Transform::from_translation(translation),
+                            ..Default::default()
+                        })
+                        .insert(particle);
+                }
+            }
+        }
+    }
+}
+
+pub fn simulate(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut emitter_query: Query<&mut ParticleEmitter>,
+    mut particle_query: Query<(
+        Entity,
+        &mut Particle,
+        &mut Transform,
+        &mut Sprite,
+        &mut Visible,
+    )>,
+) {
+    let delta = time.delta_seconds();
+    for (entity, mut particle, mut transform, mut sprite, mut visible) in particle_query.iter_mut()
+    {
+        if !particle.alive {
+            // Pooled particles of an emitter that went away go with it.
+            if emitter_query.get_mut(particle.emitter).is_err() {
+                commands.entity(entity).despawn();
+            }
+            continue;
+        }
+
+        particle.age += delta;
+        if particle.age >= particle.lifetime {
+            particle.alive = false;
+            visible.is_visible = false;
+            // Back to the emitter for reuse, or gone with it.
+            match emitter_query.get_mut(particle.emitter) {
+                Ok(mut emitter) => {
+                    emitter.alive -= 1;
+                    emitter.pool.push(entity);
+                }
+                Err(_) => commands.entity(entity).despawn(),
+            }
+            continue;
+        }
+
+        transform.translation += (particle.velocity * delta).extend(0.);
+        if particle.shrink {
+            sprite.size = particle.size * (1. - particle.age / particle.lifetime);
+        }
+    }
+}
@@ -0,0 +1,365 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+
+use super::chat::Chat;
+use super::collision::CollisionMap;
+use super::death::Respawn;
+use super::movement::{Motion, MoveIntent};
+use super::picking::Hover;
+use super::Character;
+use super::GameMap;
+use super::Player;
+use super::Position;
+
+use bevy::prelude::*;
+
+// Same eight directions as the movement keys.
+const DIRECTIONS: [(i32, i32); 8] = [
+    (0, 1),
+    (-1, 0),
+    (0, -1),
+    (1, 0),
+    (1, 1),
+    (1, -1),
+    (-1, -1),
+    (-1, 1),
+];
+const STRAIGHT_COST: i32 = 10;
+const DIAGONAL_COST: i32 = 14;
+// Gives up on goals that can't be reached instead of flooding the whole map.
+const MAX_EXPANDED: usize = 10_000;
+// Under the tile highlight, over the terrain.
+const TRAIL_Z: f32 = 1.5;
+
+pub struct TrailMaterial(Handle<ColorMaterial>);
+
+impl FromWorld for TrailMaterial {
+    fn from_world(world: &mut World) -> Self {
+        let mut materials = world.get_resource_mut::<Assets<ColorMaterial>>().unwrap();
+        TrailMaterial(materials.add(Color::rgba(1., 1., 0.6, 0.2).into()))
+    }
+}
+
+pub struct Trail;
+
+// A clicked destination the local player is walking to, one tile at a time.
+pub struct PathFollow {
+    goal: Position,
+    steps: VecDeque<Position>,
+    // One faint sprite per step still ahead, in the same order as `steps`.
+    trail: VecDeque<Entity>,
+}
+
+fn heuristic(from: (i32, i32), to: (i32, i32)) -> i32 {
+    let dx = (from.0 - to.0).abs();
+    let dy = (from.1 - to.1).abs();
+    STRAIGHT_COST * (dx + dy) + (DIAGONAL_COST - 2 * STRAIGHT_COST) * dx.min(dy)
+}
+
+// A* over the collision grid. The returned steps leave out `from` and end on
+// `to`.
+pub fn find_path(collision: &CollisionMap, from: Position, to: Position) -> Option<Vec<Position>> {
+    let start = (from.x, from.y);
+    let goal = (to.x, to.y);
+    if start == goal || !collision.is_walkable(goal.0, goal.1) {
+        return None;
+    }
+
+    let mut open = BinaryHeap::new();
+    let mut came_from: HashMap<(i32, i32), (i32, i32)> = HashMap::new();
+    let mut costs: HashMap<(i32, i32), i32> = HashMap::new();
+    open.push(Reverse((heuristic(start, goal), start)));
+    costs.insert(start, 0);
+
+    let mut expanded = 0;
+    while let Some(Reverse((_, current))) = open.pop() {
+        if current == goal {
+            let mut steps = vec![current];
+            let mut point = current;
+            while let Some(previous) = came_from.get(&point) {
+                if *previous == start {
+                    break;
+                }
+                steps.push(*previous);
+                point = *previous;
+            }
+            steps.reverse();
+
+            return Some(steps.into_iter().map(|(x, y)| Position { x, y }).collect());
+        }
+
+        expanded += 1;
+        if expanded > MAX_EXPANDED {
+            return None;
+        }
+
+        let position = Position {
+            x: current.0,
+            y: current.1,
+        };
+        for delta in DIRECTIONS.iter() {
+            if !collision.can_move(position, *delta) {
+                continue;
+            }
+
+            let next = (current.0 + delta.0, current.1 + delta.1);
+            let step_cost = if delta.0 != 0 && delta.1 != 0 {
+                DIAGONAL_COST
+            } else {
+                STRAIGHT_COST
+            };
+            let cost = costs[&current] + step_cost;
+            if costs.get(&next).map_or(true, |known| cost < *known) {
+                costs.insert(next, cost);
+                came_from.insert(next, current);
+                open.push(Reverse((cost + heuristic(next, goal), next)));
+            }
+        }
+    }
+
+    None
+}
+
+fn spawn_trail(
+    commands: &mut Commands,
+    material: &TrailMaterial,
+    steps: &[Position],
+) -> VecDeque<Entity> {
+    steps
+        .iter()
+        .map(|step| {
+            commands
+                .spawn_bundle(SpriteBundle {
+                    material: material.0.clone(),
+                    sprite: Sprite::new(Vec2::splat(8.)),
+                    transform: Transform::from_translation(step.to_world().extend(TRAIL_Z)),
+                    ..Default::default()
+                })
+                .insert(Trail)
+                .id()
+        })
+        .collect()
+}
+
+fn cancel(commands: &mut Commands, entity: Entity, path: &PathFollow) {
+    for trail in path.trail.iter() {
+        commands.entity(*trail).despawn();
+    }
+    commands.entity(entity).remove::<PathFollow>();
+}
+
+pub fn click_to_move(
+    mut commands: Commands,
+    mouse_input: Res<Input<MouseButton>>,
+    hover: Res<Hover>,
+    respawn: Res<Respawn>,
+    game_state: Res<GameMap>,
+    current_char: Res<Character>,
+    material: Res<TrailMaterial>,
+    query: Query<(Entity, &Player, &Position, Option<&PathFollow>)>,
+) {
+    if !game_state.map_loaded || !mouse_input.just_pressed(MouseButton::Left) {
+        return;
+    }
+    // Clicking on a mob targets it, see `target`.
+    if hover.mob.is_some() || respawn.is_dead() {
+        return;
+    }
+    // There's no tile under a panel, clicks on the UI stay there.
+    let goal = match hover.tile {
+        Some(tile) => tile,
+        None => return,
+    };
+
+    for (entity, player, position, path) in query.iter() {
+        if player.id != current_char.id {
+            continue;
+        }
+
+        if let Some(path) = path {
+            cancel(&mut commands, entity, path);
+        }
+        if let Some(steps) = find_path(&game_state.collision, *position, goal) {
+            let trail = spawn_trail(&mut commands, &material, &steps);
+            commands.entity(entity).insert(PathFollow {
+                goal,
+                steps: steps.into(),
+                trail,
+            });
+        }
+    }
+}
+
+// Runs after the keyboard has written the intent and before `movement::step`,
+// so a path step is moved and sent to the server like a key press.
+pub fn follow_path(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    chat: Res<Chat>,
+    respawn: Res<Respawn>,
+    game_state: Res<GameMap>,
+    material: Res<TrailMaterial>,
+    mut query: Query<(Entity, &Position, &Motion, &mut MoveIntent, &mut PathFollow)>,
+) {
+    for (entity, position, motion, mut intent, mut path) in query.iter_mut() {
+        // Typing in the chat doesn't stop the walk, dying does.
+        let key_pressed = !chat.typing && keyboard_input.get_just_pressed().next().is_some();
+        if key_pressed || respawn.is_dead() {
+            cancel(&mut commands, entity, &path);
+            continue;
+        }
+
+        while path.steps.front() == Some(position) {
+            path.steps.pop_front();
+            if let Some(trail) = path.trail.pop_front() {
+                commands.entity(trail).despawn();
+            }
+        }
+
+        let next = match path.steps.front() {
+            Some(next) => *next,
+            None => {
+                commands.entity(entity).remove::<PathFollow>();
+                continue;
+            }
+        };
+        if motion.is_moving() {
+            continue;
+        }
+
+        // Someone stepped in the way, or the server put us somewhere else.
+        let delta = (next.x - position.x, next.y - position.y);
+        let adjacent = delta.0.abs() <= 1 && delta.1.abs() <= 1;
+        if !adjacent || !game_state.collision.can_move(*position, delta) {
+            for trail in path.trail.drain(..) {
+                commands.entity(trail).despawn();
+            }
+            match find_path(&game_state.collision, *position, path.goal) {
+                Some(steps) => {
+                    path.trail = spawn_trail(&mut commands, &material, &steps);
+                    path.steps = steps.into();
+                }
+                None => {
+                    commands.entity(entity).remove::<PathFollow>();
+                }
+            }
+            continue;
+        }
+
+        intent.direction = delta;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(width: i32, height: i32, walls: &[(i32, i32)]) -> CollisionMap {
+        let mut collision = CollisionMap::new(0, 0, width, height);
+        for (x, y) in walls.iter() {
+            collision.set_walkable(*x, *y, false);
+        }
+        collision
+    }
+
+    fn at(x: i32, y: i32) -> Position {
+        Position { x, y }
+    }
+
+    // Every step is one the player could take with the keys.
+    fn assert_walkable(collision: &CollisionMap, from: Position, steps: &[Position]) {
+        let mut position = from;
+        for step in steps.iter() {
+            let delta = (step.x - position.x, step.y - position.y);
+            assert!(delta.0.abs() <= 1 && delta.1.abs() <= 1);
+            assert!(collision.can_move(position, delta));
+            position = *step;
+        }
+    }
+
+    #[test]
+    fn straight_path() {
+        let collision = map(5, 1, &[]);
+        let steps = find_path(&collision, at(0, 0), at(4, 0)).unwrap();
+        assert!(steps == vec![at(1, 0), at(2, 0), at(3, 0), at(4, 0)]);
+        assert!(find_path(&collision, at(2, 0), at(2, 0)).is_none());
+    }
+
+    #[test]
+    fn diagonal_corner_cutting() {
+        // One wall on the corner doesn't stop a diagonal step.
+        let collision = map(3, 3, &[(1, 0)]);
+        let steps = find_path(&collision, at(0, 0), at(2, 0)).unwrap();
+        assert!(steps == vec![at(1, 1), at(2, 0)]);
+
+        // Two walls on both corners do, the path goes around them.
+        let collision = map(4, 4, &[(1, 0), (0, 1), (2, 1)]);
+        let steps = find_path(&collision, at(1, 1), at(0, 0));
+        assert!(steps.is_none());
+        let steps = find_path(&collision, at(2, 0), at(1, 1)).unwrap();
+        assert!(steps.len() > 1);
+        assert_walkable(&collision, at(2, 0), &steps);
+    }
+
+    #[test]
+    fn unreachable_goal() {
+        // (3, 3) is walled in on every side.
+        let collision = map(
+            6,
+            6,
+            &[
+                (2, 2),
+                (3, 2),
+                (4, 2),
+                (2, 3),
+                (4, 3),
+                (2, 4),
+                (3, 4),
+                (4, 4),
+            ],
+        );
+        assert!(collision.is_walkable(3, 3));
+        assert!(find_path(&collision, at(0, 0), at(3, 3)).is_none());
+    }
+
+    #[test]
+    fn blocked_goal() {
+        let mut collision = map(4, 4, &[(3, 3)]);
+        assert!(find_path(&collision, at(0, 0), at(3, 3)).is_none());
+
+        collision.set_blockers(vec![(2, 2)].into_iter());
+        assert!(find_path(&collision, at(0, 0), at(2, 2)).is_none());
+        // Someone standing in the way is walked around.
+        let steps = find_path(&collision, at(1, 1), at(3, 2)).unwrap();
+        assert!(!steps.contains(&at(2, 2)));
+        assert_walkable(&collision, at(1, 1), &steps);
+    }
+
+    // A corridor winding across the whole map, every tile of it has to be
+    // expanded to get to the end.
+    fn winding(width: i32, rows: i32) -> CollisionMap {
+        let mut walls = Vec::new();
+        for row in 0..rows - 1 {
+            let y = row * 2 + 1;
+            let gap = if row % 2 == 0 { width - 1 } else { 0 };
+            for x in 0..width {
+                if x != gap {
+                    walls.push((x, y));
+                }
+            }
+        }
+        map(width, rows * 2 - 1, &walls)
+    }
+
+    #[test]
+    fn max_expanded() {
+        // About 2000 tiles long.
+        let collision = winding(100, 20);
+        let steps = find_path(&collision, at(0, 0), at(0, 38)).unwrap();
+        assert_walkable(&collision, at(0, 0), &steps);
+
+        // About 12000 tiles long, past the cut-off.
+        let collision = winding(200, 60);
+        assert!(find_path(&collision, at(0, 0), at(0, 118)).is_none());
+    }
+}
@@ -0,0 +1,177 @@
+use super::camera::FieldCamera;
+use super::emote::Emotes;
+use super::minimap::WorldMap;
+use super::mob::{self, Mob};
+use super::props::Prop;
+use super::Player;
+use super::Position;
+use super::TILE_SIZE;
+
+use bevy::{prelude::*, render::camera::OrthographicProjection};
+
+// Above the terrain and flat props, below everything y-sorted.
+const HIGHLIGHT_Z: f32 = 2.;
+
+// What the mouse is over on the field, for anything that reacts to the cursor.
+#[derive(Default)]
+pub struct Hover {
+    pub world: Option<Vec2>,
+    pub tile: Option<Position>,
+    pub player: Option<Entity>,
+    pub prop: Option<Entity>,
+    pub mob: Option<Entity>,
+}
+
+pub struct TileHighlight;
+pub struct HoverTooltip;
+// A panel on screen that hides the field under it from the cursor, like the
+// chat box or the minimap.
+pub struct UiPanel;
+
+pub fn cursor_to_world(
+    window: &Window,
+    camera_transform: &Transform,
+    projection: &OrthographicProjection,
+) -> Option<Vec2> {
+    let cursor = window.cursor_position()?;
+    let window_size = Vec2::new(window.width(), window.height());
+
+    // The cursor starts at the bottom-left corner of the window, the camera
+    // looks at the middle of it.
+    let offset = (cursor - window_size / 2.) * projection.scale;
+    Some(camera_transform.translation.truncate() + offset)
+}
+
+pub fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    commands
+        .spawn_bundle(SpriteBundle {
+            material: materials.add(Color::rgba(1., 1., 1., 0.25).into()),
+            sprite: Sprite::new(Vec2::splat(TILE_SIZE)),
+            visible: Visible {
+                is_visible: false,
+                is_transparent: true,
+            },
+            ..Default::default()
+        })
+        .insert(TileHighlight);
+
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Medium.otf"),
+                    font_size: 20.0,
+                    color: Color::WHITE,
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(HoverTooltip);
+}
+
+pub fn hover(
+    windows: Res<Windows>,
+    world_map: Res<WorldMap>,
+    emotes: Res<Emotes>,
+    mut hover: ResMut<Hover>,
+    camera_query: Query<
+        (&Transform, &OrthographicProjection),
+        (With<FieldCamera>, Without<TileHighlight>),
+    >,
+    player_query: Query<(Entity, &Player, &Position)>,
+    mob_query: Query<(Entity, &Mob, &Position)>,
+    prop_query: Query<(Entity, &Prop, &Transform), Without<TileHighlight>>,
+    mut highlight_query: Query<(&mut Transform, &mut Visible), With<TileHighlight>>,
+    mut tooltip_query: Query<(&mut Text, &mut Style), With<HoverTooltip>>,
+    panel_query: Query<
+        (&Node, &GlobalTransform, &Visible),
+        (With<UiPanel>, Without<TileHighlight>),
+    >,
+) {
+    *hover = Hover::default();
+
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    // UI nodes are laid out in window pixels, the same as the cursor.
+    let over_panel = window.cursor_position().map_or(false, |cursor| {
+        panel_query.iter().any(|(node, transform, visible)| {
+            let min = transform.translation.truncate() - node.size / 2.;
+            let max = transform.translation.truncate() + node.size / 2.;
+            visible.is_visible
+                && (min.x..max.x).contains(&cursor.x)
+                && (min.y..max.y).contains(&cursor.y)
+        })
+    });
+    // Nothing on the field is under the cursor while the world map, the
+    // emote menu or another panel covers it.
+    if world_map.open || emotes.menu_open || over_panel {
+        for (_, mut visible) in highlight_query.iter_mut() {
+            visible.is_visible = false;
+        }
+        for (mut text, _) in tooltip_query.iter_mut() {
+            text.sections[0].value.clear();
+        }
+        return;
+    }
+    if let Some((camera_transform, projection)) = camera_query.iter().next() {
+        hover.world = cursor_to_world(window, camera_transform, projection);
+    }
+
+    let mut label = String::new();
+    if let Some(world) = hover.world {
+        let tile = Position::from_world(world);
+        hover.tile = Some(tile);
+        label = format!("({}, {})", tile.x, tile.y);
+
+        if let Some((entity, prop, _)) = prop_query
+            .iter()
+            .find(|(_, prop, transform)| prop.contains(transform, world))
+        {
+            hover.prop = Some(entity);
+            label = format!("{} ({}, {})", prop.kind, prop.position.x, prop.position.y);
+        }
+
+        // Players win over props, they're what you usually point at.
+        if let Some((entity, player, position)) = player_query
+            .iter()
+            .find(|(_, _, position)| **position == tile)
+        {
+            hover.player = Some(entity);
+            label = format!("{} ({}, {})", player.name, position.x, position.y);
+        }
+        if let Some((entity, mob, position)) =
+            mob_query.iter().find(|(_, _, position)| **position == tile)
+        {
+            hover.mob = Some(entity);
+            let name = mob::mob_def(mob.kind).name;
+            label = format!("{} ({}, {})", name, position.x, position.y);
+        }
+    }
+
+    for (mut transform, mut visible) in highlight_query.iter_mut() {
+        visible.is_visible = hover.tile.is_some();
+        if let Some(tile) = hover.tile {
+            transform.translation = tile.to_world().extend(HIGHLIGHT_Z);
+        }
+    }
+
+    for (mut text, mut style) in tooltip_query.iter_mut() {
+        text.sections[0].value = label.clone();
+        if let Some(cursor) = window.cursor_position() {
+            style.position.left = Val::Px(cursor.x + 16.);
+            style.position.bottom = Val::Px(cursor.y + 16.);
+        }
+    }
+}
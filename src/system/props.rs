@@ -0,0 +1,135 @@
+use super::collision::CollisionMap;
+use super::interaction::{Interactable, Sign};
+use super::map::MapData;
+use super::Character;
+use super::Player;
+use super::Position;
+use super::PIXEL_SCALE;
+use super::TILE_SIZE;
+
+use bevy::prelude::*;
+
+// Flat props sit between the terrain and the y-sorted sprites.
+const FLAT_PROP_Z: f32 = 1.;
+const HIDING_ALPHA: f32 = 0.4;
+
+pub struct Prop {
+    pub kind: String,
+    pub position: Position,
+    flat: bool,
+    // World size of the sprite, used to tell whether it hides someone.
+    size: Vec2,
+}
+
+impl Prop {
+    pub fn contains(&self, transform: &Transform, point: Vec2) -> bool {
+        let center = transform.translation.truncate();
+        (point - center).abs().cmple(self.size / 2.).all()
+    }
+}
+
+pub fn spawn_props(
+    commands: &mut Commands,
+    data: &MapData,
+    collision: &mut CollisionMap,
+    texture_atlas_handle: &Handle<TextureAtlas>,
+    texture_atlas: &TextureAtlas,
+    asset_server: &AssetServer,
+) {
+    for placed in data.props.iter() {
+        // Downloaded maps can name props this client doesn't know.
+        let kind = match data.prop_kinds.get(&placed.kind) {
+            Some(kind) => kind,
+            None => {
+                warn!("Skipping prop of unknown kind {}", placed.kind);
+                continue;
+            }
+        };
+        let handle: Handle<Texture> = asset_server.get_handle(kind.texture.as_str());
+        let sprite_index = match texture_atlas.get_texture_index(&handle) {
+            Some(sprite_index) => sprite_index,
+            None => {
+                warn!("Skipping prop {}, no texture {}", placed.kind, kind.texture);
+                continue;
+            }
+        };
+        let rect = texture_atlas.textures[sprite_index];
+        let texture_size = Vec2::new(rect.width(), rect.height());
+        let anchor = kind
+            .anchor
+            .map_or(Vec2::new(texture_size.x / 2., 0.), |(x, y)| Vec2::new(x, y));
+
+        // The anchor stands on the bottom edge of the tile, the sprite itself
+        // is drawn around its centre.
+        let position = Position {
+            x: placed.x,
+            y: placed.y,
+        };
+        let ground = position.to_world() - Vec2::new(0., TILE_SIZE / 2.);
+        let center = ground + (texture_size / 2. - anchor) * PIXEL_SCALE;
+        let z = if kind.flat {
+            FLAT_PROP_Z
+        } else {
+            super::y_sort(ground)
+        };
+
+        for (dx, dy) in kind.walkable.iter() {
+            collision.set_walkable(placed.x + dx, placed.y + dy, true);
+        }
+        for (dx, dy) in kind.footprint.iter() {
+            collision.set_walkable(placed.x + dx, placed.y + dy, false);
+        }
+
+        let mut entity = commands.spawn_bundle(SpriteSheetBundle {
+            texture_atlas: texture_atlas_handle.clone(),
+            sprite: TextureAtlasSprite::new(sprite_index as u32),
+            transform: Transform {
+                translation: center.extend(z),
+                scale: Vec3::splat(PIXEL_SCALE),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        entity.insert(Prop {
+            kind: placed.kind.clone(),
+            position,
+            flat: kind.flat,
+            size: texture_size * PIXEL_SCALE,
+        });
+        if let Some(interaction) = kind.interaction {
+            entity
+                .insert(Interactable {
+                    kind: interaction,
+                    name: placed.kind.clone(),
+                })
+                .insert(position);
+        }
+        if let Some(text) = &placed.text {
+            entity.insert(Sign { text: text.clone() });
+        }
+    }
+}
+
+pub fn fade_props(
+    current_char: Res<Character>,
+    player_query: Query<(&Player, &Transform)>,
+    mut prop_query: Query<(&Prop, &Transform, &mut TextureAtlasSprite)>,
+) {
+    let player_transform = match player_query
+        .iter()
+        .find(|(player, _)| player.id == current_char.id)
+    {
+        Some((_, transform)) => transform,
+        None => return,
+    };
+    let player_point = player_transform.translation.truncate();
+
+    for (prop, transform, mut sprite) in prop_query.iter_mut() {
+        let covers = prop.contains(transform, player_point);
+        let in_front = !prop.flat && transform.translation.z > player_transform.translation.z;
+
+        sprite
+            .color
+            .set_a(if covers && in_front { HIDING_ALPHA } else { 1. });
+    }
+}
@@ -0,0 +1,243 @@
+use std::cmp::Ordering;
+
+use super::chat::Chat;
+use super::combat::Dead;
+use super::dialogue::Dialogue;
+use super::emote::Emotes;
+use super::mob::{self, Mob};
+use super::movement::Motion;
+use super::picking::{Hover, UiPanel};
+use super::Character;
+use super::Health;
+use super::Player;
+use super::TILE_SIZE;
+
+use bevy::prelude::*;
+
+// Under the hover highlight, over the flat props.
+const MARKER_Z: f32 = 1.9;
+// Tab only picks mobs closer than this.
+const MAX_DISTANCE: f32 = TILE_SIZE * 10.;
+const FRAME_WIDTH: f32 = 240.;
+const BAR_HEIGHT: f32 = 10.;
+const FONT_SIZE: f32 = 18.;
+
+// The mob we're fighting, picked with Tab or by clicking on it.
+#[derive(Default)]
+pub struct Target {
+    pub entity: Option<Entity>,
+}
+
+pub struct TargetFrame;
+pub struct TargetText;
+pub struct TargetBar;
+pub struct TargetBarFill;
+pub struct TargetMarker;
+
+pub fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let hidden = Visible {
+        is_visible: false,
+        is_transparent: true,
+    };
+    commands
+        .spawn_bundle(SpriteBundle {
+            material: materials.add(Color::rgba(1., 0.2, 0.2, 0.35).into()),
+            sprite: Sprite::new(Vec2::splat(TILE_SIZE)),
+            visible: hidden.clone(),
+            ..Default::default()
+        })
+        .insert(TargetMarker);
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(10.),
+                    ..Default::default()
+                },
+                size: Size::new(Val::Percent(100.), Val::Auto),
+                justify_content: JustifyContent::Center,
+                ..Default::default()
+            },
+            material: materials.add(Color::NONE.into()),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(FRAME_WIDTH), Val::Auto),
+                        // Children go from the bottom up, the bar first.
+                        flex_direction: FlexDirection::Column,
+                        padding: Rect::all(Val::Px(6.)),
+                        ..Default::default()
+                    },
+                    material: materials.add(Color::rgba(0., 0., 0., 0.6).into()),
+                    visible: hidden.clone(),
+                    ..Default::default()
+                })
+                .insert(TargetFrame)
+                .insert(UiPanel)
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle(NodeBundle {
+                            style: Style {
+                                size: Size::new(Val::Percent(100.), Val::Px(BAR_HEIGHT)),
+                                ..Default::default()
+                            },
+                            material: materials.add(Color::rgba(0.2, 0.05, 0.05, 1.).into()),
+                            visible: hidden.clone(),
+                            ..Default::default()
+                        })
+                        .insert(TargetBar)
+                        .with_children(|parent| {
+                            parent
+                                .spawn_bundle(NodeBundle {
+                                    style: Style {
+                                        size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                                        ..Default::default()
+                                    },
+                                    material: materials.add(Color::rgb(0.85, 0.2, 0.2).into()),
+                                    visible: hidden.clone(),
+                                    ..Default::default()
+                                })
+                                .insert(TargetBarFill);
+                        });
+                    parent
+                        .spawn_bundle(TextBundle {
+                            style: Style {
+                                margin: Rect {
+                                    bottom: Val::Px(4.),
+                                    ..Default::default()
+                                },
+                                ..Default::default()
+                            },
+                            text: Text::with_section(
+                                "",
+                                TextStyle {
+                                    font: asset_server.load("fonts/FiraSans-Bold.otf"),
+                                    font_size: FONT_SIZE,
+                                    color: Color::WHITE,
+                                },
+                                Default::default(),
+                            ),
+                            visible: hidden,
+                            ..Default::default()
+                        })
+                        .insert(TargetText);
+                });
+        });
+}
+
+// Tab goes through the mobs around us from the closest one, clicking on a mob
+// targets it and Escape lets go.
+pub fn select(
+    keyboard_input: Res<Input<KeyCode>>,
+    mouse_input: Res<Input<MouseButton>>,
+    hover: Res<Hover>,
+    current_char: Res<Character>,
+    chat: Res<Chat>,
+    dialogue: Res<Dialogue>,
+    emotes: Res<Emotes>,
+    mut target: ResMut<Target>,
+    player_query: Query<(&Player, &Motion)>,
+    mob_query: Query<(Entity, &Motion), (With<Mob>, Without<Dead>)>,
+) {
+    if mouse_input.just_pressed(MouseButton::Left) && hover.mob.is_some() {
+        target.entity = hover.mob;
+    }
+    if chat.typing || dialogue.is_open() || emotes.menu_open {
+        return;
+    }
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        target.entity = None;
+    }
+    if !keyboard_input.just_pressed(KeyCode::Tab) {
+        return;
+    }
+
+    let ours = match player_query
+        .iter()
+        .find(|(player, _)| player.id == current_char.id)
+    {
+        Some((_, motion)) => motion.current(),
+        None => return,
+    };
+    let mut nearby: Vec<(Entity, f32)> = mob_query
+        .iter()
+        .map(|(entity, motion)| (entity, motion.current().distance(ours)))
+        .filter(|(_, distance)| *distance <= MAX_DISTANCE)
+        .collect();
+    nearby.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal));
+
+    let next = match nearby
+        .iter()
+        .position(|(entity, _)| Some(*entity) == target.entity)
+    {
+        Some(n) => n + 1,
+        None => 0,
+    };
+    target.entity = nearby
+        .get(next)
+        .or_else(|| nearby.first())
+        .map(|(entity, _)| *entity);
+}
+
+pub fn update_frame(
+    mut target: ResMut<Target>,
+    mob_query: Query<(&Mob, &Motion, &Health)>,
+    mut marker_query: Query<&mut Transform, With<TargetMarker>>,
+    mut text_query: Query<&mut Text, With<TargetText>>,
+    mut fill_query: Query<&mut Style, With<TargetBarFill>>,
+    mut visible_query: Query<
+        &mut Visible,
+        Or<(
+            With<TargetMarker>,
+            With<TargetFrame>,
+            With<TargetText>,
+            With<TargetBar>,
+            With<TargetBarFill>,
+        )>,
+    >,
+) {
+    // Targets that despawned, e.g. left the zone, are let go.
+    let found = target.entity.and_then(|entity| mob_query.get(entity).ok());
+    if found.is_none() {
+        target.entity = None;
+    }
+    for mut visible in visible_query.iter_mut() {
+        visible.is_visible = found.is_some();
+    }
+    let (mob, motion, health) = match found {
+        Some(found) => found,
+        None => return,
+    };
+
+    for mut transform in marker_query.iter_mut() {
+        transform.translation = motion.current().extend(MARKER_Z);
+    }
+    let name = mob::mob_def(mob.kind).name;
+    let label = if health.max == 0 {
+        name.to_string()
+    } else {
+        format!("{}  {}/{}", name, health.current, health.max)
+    };
+    for mut text in text_query.iter_mut() {
+        if text.sections[0].value != label {
+            text.sections[0].value = label.clone();
+        }
+    }
+    let ratio = if health.max == 0 {
+        1.
+    } else {
+        health.current as f32 / health.max as f32
+    };
+    for mut style in fill_query.iter_mut() {
+        style.size.width = Val::Percent(ratio * 100.);
+    }
+}
@@ -0,0 +1,286 @@
+use std::collections::HashMap;
+use std::fs;
+
+use super::map::{MapData, PlacedTile, TileKind};
+
+use serde::{Deserialize, Serialize};
+
+pub const TERRAIN_PATH: &str = "assets/map/terrain.ron";
+
+// Below this elevation is water, at and above it a plateau whose south edge
+// drops down as a cliff.
+const WATER_LEVEL: f32 = 0.32;
+const CLIFF_LEVEL: f32 = 0.62;
+// How close to the middle of the path noise counts as path, wider is wider
+// paths.
+const PATH_WIDTH: f32 = 0.015;
+// Tiles around the spawn point that are always plain grass.
+const SPAWN_CLEARING: i32 = 3;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum Terrain {
+    Grass,
+    Water,
+    Path,
+    Cliff,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Autotile {
+    // Neighbours of this terrain set the bits of the mask.
+    pub against: Terrain,
+    // Kind to use for a mask of north = 1, east = 2, south = 4, west = 8.
+    // Masks that aren't listed or give the first variant use the plain
+    // variants.
+    pub masks: HashMap<u8, String>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TerrainTiles {
+    // The first variant is the common one, the others are sprinkled in.
+    pub variants: Vec<String>,
+    #[serde(default)]
+    pub autotile: Option<Autotile>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Tileset {
+    pub kinds: HashMap<String, TileKind>,
+    pub terrains: HashMap<Terrain, TerrainTiles>,
+}
+
+impl Tileset {
+    pub fn load(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = fs::read_to_string(path)?;
+        Ok(ron::de::from_str(&content)?)
+    }
+}
+
+// Only integer maths and plain float arithmetic from here on, so every client
+// gets the exact same world out of the same seed.
+fn hash(seed: u64, x: i32, y: i32) -> u64 {
+    let mut h = seed
+        ^ (x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ (y as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F);
+    h = (h ^ (h >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    h = (h ^ (h >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    h ^ (h >> 31)
+}
+
+fn lattice(seed: u64, x: i32, y: i32) -> f32 {
+    (hash(seed, x, y) >> 40) as f32 / (1u64 << 24) as f32
+}
+
+fn smooth(t: f32) -> f32 {
+    t * t * (3. - 2. * t)
+}
+
+// Value noise in [0, 1) with a lattice point every `scale` tiles.
+fn value_noise(seed: u64, x: i32, y: i32, scale: i32) -> f32 {
+    let cell_x = x.div_euclid(scale);
+    let cell_y = y.div_euclid(scale);
+    let tx = smooth(x.rem_euclid(scale) as f32 / scale as f32);
+    let ty = smooth(y.rem_euclid(scale) as f32 / scale as f32);
+
+    let a = lattice(seed, cell_x, cell_y);
+    let b = lattice(seed, cell_x + 1, cell_y);
+    let c = lattice(seed, cell_x, cell_y + 1);
+    let d = lattice(seed, cell_x + 1, cell_y + 1);
+    let top = a + (b - a) * tx;
+    let bottom = c + (d - c) * tx;
+    top + (bottom - top) * ty
+}
+
+// A few octaves of value noise, halving the scale and the weight each time.
+fn fractal_noise(seed: u64, x: i32, y: i32, scale: i32) -> f32 {
+    let mut total = 0.;
+    let mut weight = 0.5;
+    let mut weights = 0.;
+    let mut scale = scale;
+    for octave in 0..4 {
+        total += value_noise(seed.wrapping_add(octave), x, y, scale) * weight;
+        weights += weight;
+        weight /= 2.;
+        scale = (scale / 2).max(1);
+    }
+    total / weights
+}
+
+pub struct Generator {
+    seed: u64,
+    spawn: (i32, i32),
+}
+
+impl Generator {
+    pub fn new(seed: u64, spawn: (i32, i32)) -> Self {
+        Self { seed, spawn }
+    }
+
+    fn elevation(&self, x: i32, y: i32) -> f32 {
+        fractal_noise(self.seed, x, y, 48)
+    }
+
+    // Terrain of a single tile. It only depends on the seed and the point, so
+    // any chunk can be generated without the ones around it.
+    pub fn terrain_at(&self, x: i32, y: i32) -> Terrain {
+        if (x - self.spawn.0).abs() <= SPAWN_CLEARING && (y - self.spawn.1).abs() <= SPAWN_CLEARING
+        {
+            return Terrain::Grass;
+        }
+
+        let elevation = self.elevation(x, y);
+        if elevation < WATER_LEVEL {
+            return Terrain::Water;
+        }
+        if elevation >= CLIFF_LEVEL && self.elevation(x, y - 1) < CLIFF_LEVEL {
+            return Terrain::Cliff;
+        }
+
+        let path = fractal_noise(self.seed.wrapping_add(100), x, y, 32);
+        if (path - 0.5).abs() < PATH_WIDTH {
+            return Terrain::Path;
+        }
+
+        Terrain::Grass
+    }
+
+    // Terrain of a chunk with a one tile border, so the edges can look at
+    // their neighbours.
+    fn chunk_terrain(&self, min_x: i32, min_y: i32, width: i32, height: i32) -> Vec<Terrain> {
+        let mut terrain = Vec::with_capacity(((width + 2) * (height + 2)) as usize);
+        for y in min_y - 1..min_y + height + 1 {
+            for x in min_x - 1..min_x + width + 1 {
+                terrain.push(self.terrain_at(x, y));
+            }
+        }
+        terrain
+    }
+
+    // Tiles of one chunk of the map. Plain tiles of the first grass variant
+    // are left out, they're the map's fill.
+    pub fn generate_chunk(
+        &self,
+        tileset: &Tileset,
+        min_x: i32,
+        min_y: i32,
+        width: i32,
+        height: i32,
+    ) -> Vec<PlacedTile> {
+        let terrain = self.chunk_terrain(min_x, min_y, width, height);
+        let at = |x: i32, y: i32| {
+            let index = (y - min_y + 1) * (width + 2) + (x - min_x + 1);
+            terrain[index as usize]
+        };
+        let fill = &tileset.terrains[&Terrain::Grass].variants[0];
+
+        let mut tiles = Vec::new();
+        for y in min_y..min_y + height {
+            for x in min_x..min_x + width {
+                let tiles_of = &tileset.terrains[&at(x, y)];
+
+                let autotiled = tiles_of.autotile.as_ref().and_then(|autotile| {
+                    let neighbours = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+                    let mut mask = 0;
+                    for (bit, (dx, dy)) in neighbours.iter().enumerate() {
+                        if at(x + dx, y + dy) == autotile.against {
+                            mask |= 1 << bit;
+                        }
+                    }
+                    autotile
+                        .masks
+                        .get(&mask)
+                        .filter(|kind| **kind != tiles_of.variants[0])
+                });
+                let kind = match autotiled {
+                    Some(kind) => kind,
+                    None => {
+                        // Mostly the first variant, now and then another one.
+                        let variants = &tiles_of.variants;
+                        let roll = hash(self.seed.wrapping_add(200), x, y);
+                        if roll % 4 == 0 && variants.len() > 1 {
+                            &variants[1 + (roll / 4) as usize % (variants.len() - 1)]
+                        } else {
+                            &variants[0]
+                        }
+                    }
+                };
+
+                if kind == fill {
+                    continue;
+                }
+                tiles.push(PlacedTile {
+                    x,
+                    y,
+                    kind: kind.clone(),
+                });
+            }
+        }
+        tiles
+    }
+}
+
+// Replaces the fill of a map that asks for generated terrain. Authored tiles
+// stay on top of the generated ones.
+pub fn apply(data: &mut MapData, tileset: &Tileset, chunk_width: i32, chunk_height: i32) {
+    let seed = match &data.generator {
+        Some(generator) => generator.seed,
+        None => return,
+    };
+    let generator = Generator::new(seed, data.spawn);
+
+    let mut tiles = Vec::new();
+    let mut chunk_y = data.min_y();
+    while chunk_y < data.min_y() + data.height {
+        let height = chunk_height.min(data.min_y() + data.height - chunk_y);
+        let mut chunk_x = data.min_x();
+        while chunk_x < data.min_x() + data.width {
+            let width = chunk_width.min(data.min_x() + data.width - chunk_x);
+            tiles.extend(generator.generate_chunk(tileset, chunk_x, chunk_y, width, height));
+            chunk_x += chunk_width;
+        }
+        chunk_y += chunk_height;
+    }
+    tiles.extend(data.tiles.drain(..));
+
+    for (name, kind) in tileset.kinds.iter() {
+        data.kinds
+            .entry(name.clone())
+            .or_insert_with(|| kind.clone());
+    }
+    data.fill = tileset.terrains[&Terrain::Grass].variants[0].clone();
+    data.tiles = tiles;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn every_mask_has_a_tile() {
+        let tileset = Tileset::load(TERRAIN_PATH).unwrap();
+        for (terrain, tiles) in tileset.terrains.iter() {
+            let mut kinds = tiles.variants.clone();
+            if let Some(autotile) = &tiles.autotile {
+                for mask in 0..16 {
+                    match autotile.masks.get(&mask) {
+                        Some(kind) => kinds.push(kind.clone()),
+                        None => panic!("{:?} has no tile for mask {}", terrain, mask),
+                    }
+                }
+            }
+
+            for kind in kinds {
+                let frames = match tileset.kinds.get(&kind) {
+                    Some(found) => &found.frames,
+                    None => panic!("{:?} uses unknown kind {}", terrain, kind),
+                };
+                assert!(!frames.is_empty(), "{} has no frames", kind);
+                for frame in frames {
+                    let path = format!("assets/{}", frame.texture);
+                    assert!(Path::new(&path).exists(), "{} is missing", path);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,337 @@
+use super::camera::FieldCamera;
+use super::particles::ParticleEmitter;
+use super::Character;
+use super::GameMap;
+use super::Player;
+use super::Position;
+use super::TILE_SIZE;
+use crate::pursuit::api::mortalkin::{self, WeatherKind};
+
+use bevy::{
+    prelude::*,
+    render::{
+        camera::OrthographicProjection,
+        texture::{Extent3d, TextureDimension, TextureFormat},
+    },
+};
+
+// Over the lights, under the fog.
+const WEATHER_Z: f32 = 170.;
+const PUDDLE_Z: f32 = 1.2;
+// Intensity gained or lost per second, so weather never switches at once.
+const RAMP_PER_SECOND: f32 = 0.1;
+// How long the ground takes to get fully wet in the rain, and to dry.
+const WETTING_SECONDS: f32 = 20.;
+const DRYING_SECONDS: f32 = 60.;
+// Puddles are laid out on the tiles this far around the player, and again
+// once the player walked half of it.
+const PUDDLE_RADIUS: i32 = 16;
+// One in this many walkable tiles gets a puddle.
+const PUDDLE_ODDS: u64 = 14;
+
+struct EmitterSettings {
+    // Particles per second at full intensity.
+    rate: f32,
+    lifetime: f32,
+    velocity: Vec2,
+    velocity_spread: Vec2,
+    size: Vec2,
+    max_particles: usize,
+}
+
+fn settings(kind: WeatherKind) -> EmitterSettings {
+    match kind {
+        WeatherKind::Rain => EmitterSettings {
+            rate: 400.,
+            lifetime: 0.45,
+            velocity: Vec2::new(-90., -900.),
+            velocity_spread: Vec2::new(10., 60.),
+            size: Vec2::new(2., 18.),
+            max_particles: 240,
+        },
+        WeatherKind::Snow => EmitterSettings {
+            rate: 90.,
+            lifetime: 4.,
+            velocity: Vec2::new(-15., -70.),
+            velocity_spread: Vec2::new(30., 15.),
+            size: Vec2::new(5., 5.),
+            max_particles: 400,
+        },
+        WeatherKind::Fog | WeatherKind::Clear => EmitterSettings {
+            rate: 3.,
+            lifetime: 10.,
+            velocity: Vec2::new(14., 2.),
+            velocity_spread: Vec2::new(6., 3.),
+            size: Vec2::new(TILE_SIZE * 8., TILE_SIZE * 5.),
+            max_particles: 32,
+        },
+    }
+}
+
+pub struct Weather {
+    // What is falling right now and how much.
+    kind: WeatherKind,
+    intensity: f32,
+    // What the server last announced for our zone.
+    target_kind: WeatherKind,
+    target_intensity: f32,
+    // How wet the ground is, from 0 to 1.
+    wetness: f32,
+}
+
+impl Default for Weather {
+    fn default() -> Self {
+        Self {
+            kind: WeatherKind::Clear,
+            intensity: 0.,
+            target_kind: WeatherKind::Clear,
+            target_intensity: 0.,
+            wetness: 0.,
+        }
+    }
+}
+
+impl Weather {
+    pub fn announce(&mut self, kind: WeatherKind, intensity: f32) {
+        self.target_kind = kind;
+        self.target_intensity = intensity.max(0.).min(1.);
+    }
+
+    // Moves towards the announced weather. Different weather first dies down
+    // completely, then the new one builds up.
+    fn ramp(&mut self, delta: f32) {
+        let step = RAMP_PER_SECOND * delta;
+        if self.kind != self.target_kind {
+            self.intensity = (self.intensity - step).max(0.);
+            if self.intensity <= 0. {
+                self.kind = self.target_kind;
+            }
+            return;
+        }
+
+        if self.intensity < self.target_intensity {
+            self.intensity = (self.intensity + step).min(self.target_intensity);
+        } else {
+            self.intensity = (self.intensity - step).max(self.target_intensity);
+        }
+    }
+}
+
+pub struct WeatherEmitter {
+    kind: WeatherKind,
+}
+
+pub struct Puddle;
+
+pub struct Puddles {
+    material: Handle<ColorMaterial>,
+    // Tile the current puddles were laid out around.
+    center: Option<Position>,
+}
+
+fn new_texture(width: u32, height: u32, pixel: impl Fn(f32, f32) -> [u8; 4]) -> Texture {
+    let mut texture = Texture::new_fill(
+        Extent3d::new(width, height, 1),
+        TextureDimension::D2,
+        &[0, 0, 0, 0],
+        TextureFormat::Rgba8UnormSrgb,
+    );
+    for y in 0..height {
+        for x in 0..width {
+            // -1 to 1 across the texture.
+            let u = (x as f32 + 0.5) / width as f32 * 2. - 1.;
+            let v = (y as f32 + 0.5) / height as f32 * 2. - 1.;
+            let index = ((y * width + x) * 4) as usize;
+            texture.data[index..index + 4].copy_from_slice(&pixel(u, v));
+        }
+    }
+    texture
+}
+
+// A soft round blob, opaque in the middle.
+fn soft(u: f32, v: f32, colour: [u8; 3], alpha: f32) -> [u8; 4] {
+    let falloff = (1. - (u * u + v * v).sqrt()).max(0.);
+    [
+        colour[0],
+        colour[1],
+        colour[2],
+        (falloff * alpha * 255.) as u8,
+    ]
+}
+
+impl FromWorld for Puddles {
+    fn from_world(world: &mut World) -> Self {
+        let puddle = {
+            let mut textures = world.get_resource_mut::<Assets<Texture>>().unwrap();
+            textures.add(new_texture(14, 8, |u, v| soft(u, v, [70, 90, 130], 0.8)))
+        };
+        let mut materials = world.get_resource_mut::<Assets<ColorMaterial>>().unwrap();
+        Self {
+            material: materials.add(puddle.into()),
+            center: None,
+        }
+    }
+}
+
+// Weather the server announced for our zone.
+pub fn receive(mut events: EventReader<mortalkin::Weather>, mut weather: ResMut<Weather>) {
+    for zone_weather in events.iter() {
+        let kind = WeatherKind::from_i32(zone_weather.kind).unwrap_or(WeatherKind::Clear);
+        weather.announce(kind, zone_weather.intensity);
+    }
+}
+
+pub fn setup(
+    mut commands: Commands,
+    mut textures: ResMut<Assets<Texture>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let kinds = vec![
+        (
+            WeatherKind::Rain,
+            // A streak that's brightest at its lower end.
+            new_texture(2, 16, |_, v| [180, 200, 230, ((1. + v) * 90.) as u8]),
+        ),
+        (
+            WeatherKind::Snow,
+            new_texture(8, 8, |u, v| soft(u, v, [255, 255, 255], 1.)),
+        ),
+        (
+            WeatherKind::Fog,
+            new_texture(32, 32, |u, v| soft(u, v, [220, 225, 230], 0.35)),
+        ),
+    ];
+
+    for (n, (kind, texture)) in kinds.into_iter().enumerate() {
+        let settings = settings(kind);
+        let material = materials.add(textures.add(texture).into());
+        let mut emitter = ParticleEmitter::new(material, settings.size, 0x5EED + n as u64);
+        emitter.lifetime = settings.lifetime;
+        emitter.velocity = settings.velocity;
+        emitter.velocity_spread = settings.velocity_spread;
+        emitter.max_particles = settings.max_particles;
+        commands
+            .spawn()
+            .insert(emitter)
+            .insert(WeatherEmitter { kind })
+            .insert(Transform::from_translation(Vec3::new(0., 0., WEATHER_Z)))
+            .insert(GlobalTransform::default());
+    }
+}
+
+// Keeps the emitters over whatever the camera sees, emitting as much as the
+// weather calls for.
+pub fn update(
+    time: Res<Time>,
+    windows: Res<Windows>,
+    mut weather: ResMut<Weather>,
+    camera_query: Query<(&Transform, &OrthographicProjection), With<FieldCamera>>,
+    mut emitter_query: Query<
+        (&WeatherEmitter, &mut ParticleEmitter, &mut Transform),
+        Without<FieldCamera>,
+    >,
+) {
+    let delta = time.delta_seconds();
+    weather.ramp(delta);
+    if weather.kind == WeatherKind::Rain {
+        weather.wetness = (weather.wetness + weather.intensity * delta / WETTING_SECONDS).min(1.);
+    } else {
+        weather.wetness = (weather.wetness - delta / DRYING_SECONDS).max(0.);
+    }
+
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    let (camera, projection) = match camera_query.iter().next() {
+        Some(camera) => camera,
+        None => return,
+    };
+    let half_view = Vec2::new(window.width(), window.height()) / 2. * projection.scale;
+
+    for (weather_emitter, mut emitter, mut transform) in emitter_query.iter_mut() {
+        let settings = settings(weather_emitter.kind);
+        emitter.rate = if weather.kind == weather_emitter.kind {
+            settings.rate * weather.intensity
+        } else {
+            0.
+        };
+        // Rain and snow start a bit above the view, so they fall into it
+        // rather than pop up all over it.
+        let lead = -settings.velocity.y * settings.lifetime * 0.3;
+        emitter.area = half_view + Vec2::new(TILE_SIZE * 2., lead / 2.);
+        transform.translation.x = camera.translation.x;
+        transform.translation.y = camera.translation.y + lead / 2.;
+    }
+}
+
+fn has_puddle(x: i32, y: i32) -> bool {
+    let h = (x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15) ^ (y as u64).wrapping_mul(0xC2B2_AE3D);
+    (h ^ (h >> 29)) % PUDDLE_ODDS == 0
+}
+
+// Maps that ask for it get puddles while the ground is wet.
+pub fn puddles(
+    mut commands: Commands,
+    weather: Res<Weather>,
+    mut puddles: ResMut<Puddles>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    game_state: Res<GameMap>,
+    current_char: Res<Character>,
+    player_query: Query<(&Player, &Position)>,
+    puddle_query: Query<Entity, With<Puddle>>,
+) {
+    if let Some(material) = materials.get_mut(&puddles.material) {
+        material.color.set_a(weather.wetness);
+    }
+
+    let center = player_query
+        .iter()
+        .find(|(player, _)| player.id == current_char.id)
+        .map(|(_, position)| *position);
+    let wanted = game_state.map_loaded && game_state.data.puddles && weather.wetness > 0.;
+    let (center, wanted) = match center {
+        Some(center) if wanted => (center, true),
+        _ => (Position::default(), false),
+    };
+
+    let moved_away = puddles.center.map_or(true, |last| {
+        (last.x - center.x).abs() > PUDDLE_RADIUS / 2
+            || (last.y - center.y).abs() > PUDDLE_RADIUS / 2
+    });
+    if wanted && !moved_away {
+        return;
+    }
+    if puddles.center.is_some() {
+        for entity in puddle_query.iter() {
+            commands.entity(entity).despawn();
+        }
+        puddles.center = None;
+    }
+    if !wanted {
+        return;
+    }
+
+    for y in center.y - PUDDLE_RADIUS..=center.y + PUDDLE_RADIUS {
+        for x in center.x - PUDDLE_RADIUS..=center.x + PUDDLE_RADIUS {
+            let collision = &game_state.collision;
+            if !has_puddle(x, y) || !collision.in_bounds(x, y) || collision.is_wall(x, y) {
+                continue;
+            }
+
+            let point = Position { x, y }.to_world();
+            commands
+                .spawn_bundle(SpriteBundle {
+                    material: puddles.material.clone(),
+                    transform: Transform {
+                        translation: point.extend(PUDDLE_Z),
+                        scale: Vec3::splat(super::PIXEL_SCALE),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .insert(Puddle);
+        }
+    }
+    puddles.center = Some(center);
+}
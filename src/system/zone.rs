@@ -0,0 +1,211 @@
+use super::combat::DamageNumber;
+use super::field::AnimatedTiles;
+use super::interaction::Interactable;
+use super::lighting::PointLight;
+use super::map::ObjectKind;
+use super::mob::Mob;
+use super::movement::Motion;
+use super::npc::Npc;
+use super::pathfinding::Trail;
+use super::props::Prop;
+use super::Character;
+use super::GameMap;
+use super::Player;
+use super::Position;
+use super::RequestSender;
+use super::Token;
+use crate::pursuit::api::mortalkin;
+
+use bevy::prelude::*;
+
+use bevy_tilemap::prelude::*;
+
+const FADE_SECONDS: f32 = 0.4;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Phase {
+    Idle,
+    FadingOut,
+    // The old zone is being despawned, this lasts a single frame so nothing
+    // builds the new map on top of the old tilemap.
+    Unloading,
+    FadingIn,
+}
+
+pub struct ZoneTransition {
+    phase: Phase,
+    alpha: f32,
+    destination: Option<(u32, Position)>,
+    // Portal we already asked the server about, so standing on it doesn't
+    // ask again every frame.
+    requested: Option<Position>,
+}
+
+impl Default for ZoneTransition {
+    fn default() -> Self {
+        Self {
+            phase: Phase::Idle,
+            alpha: 0.,
+            destination: None,
+            requested: None,
+        }
+    }
+}
+
+impl ZoneTransition {
+    pub fn start(&mut self, zone_id: u32, position: Position) {
+        if self.in_progress() {
+            return;
+        }
+
+        self.phase = Phase::FadingOut;
+        self.destination = Some((zone_id, position));
+    }
+
+    pub fn in_progress(&self) -> bool {
+        self.phase != Phase::Idle
+    }
+}
+
+pub struct FadeOverlay;
+
+pub fn setup(mut commands: Commands, mut materials: ResMut<Assets<ColorMaterial>>) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                ..Default::default()
+            },
+            material: materials.add(Color::rgba(0., 0., 0., 0.).into()),
+            ..Default::default()
+        })
+        .insert(FadeOverlay);
+}
+
+pub fn enter_portals(
+    current_char: Res<Character>,
+    game_state: Res<GameMap>,
+    mut transition: ResMut<ZoneTransition>,
+    request_sender: Res<RequestSender>,
+    token: Res<Token>,
+    query: Query<(&Player, &Position, &Motion)>,
+) {
+    if !game_state.map_loaded || transition.in_progress() {
+        return;
+    }
+
+    for (player, position, motion) in query.iter() {
+        if player.id != current_char.id || motion.is_moving() {
+            continue;
+        }
+        if transition.requested == Some(*position) {
+            continue;
+        }
+        transition.requested = None;
+
+        let portal = match game_state.data.portal_at(position.x, position.y) {
+            Some(portal) => portal,
+            None => continue,
+        };
+        let destination = match portal.kind {
+            ObjectKind::Portal { zone, x, y } => mortalkin::Position {
+                x,
+                y,
+                zone_id: zone,
+            },
+        };
+
+        // The server checks the portal and answers with our character in the
+        // other zone, `incoming_notif` starts the transition from there.
+        request_sender
+            .tx
+            .lock()
+            .unwrap()
+            .unbounded_send(mortalkin::PlayGamePayload {
+                token: token.token.clone(),
+                character_id: player.id,
+                position: None,
+                enter_portal: Some(mortalkin::EnterPortal {
+                    portal: Some(mortalkin::Position {
+                        x: position.x,
+                        y: position.y,
+                        zone_id: current_char.zone_id,
+                    }),
+                    destination: Some(destination),
+                }),
+                chat: None,
+                emote: None,
+                interact: None,
+                attack: None,
+            })
+            .unwrap();
+        transition.requested = Some(*position);
+    }
+}
+
+pub fn transition(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut transition: ResMut<ZoneTransition>,
+    mut game_state: ResMut<GameMap>,
+    mut animated_tiles: ResMut<AnimatedTiles>,
+    mut current_char: ResMut<Character>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    overlay_query: Query<&Handle<ColorMaterial>, With<FadeOverlay>>,
+    field_query: Query<
+        Entity,
+        Or<(
+            With<Tilemap>,
+            With<Player>,
+            With<Prop>,
+            With<Trail>,
+            With<PointLight>,
+            With<Npc>,
+            With<Interactable>,
+            With<Mob>,
+            With<DamageNumber>,
+        )>,
+    >,
+) {
+    let step = time.delta_seconds() / FADE_SECONDS;
+    match transition.phase {
+        Phase::Idle => return,
+        Phase::FadingOut => {
+            transition.alpha = (transition.alpha + step).min(1.);
+            if transition.alpha >= 1. {
+                for entity in field_query.iter() {
+                    commands.entity(entity).despawn_recursive();
+                }
+                transition.phase = Phase::Unloading;
+            }
+        }
+        Phase::Unloading => {
+            // `field::load` and `field::build` bring the new zone up from
+            // here, with the local player on the destination tile.
+            let (zone_id, position) = transition.destination.take().unwrap();
+            current_char.zone_id = zone_id;
+            *game_state = GameMap {
+                arrival: Some(position),
+                ..Default::default()
+            };
+            *animated_tiles = AnimatedTiles::default();
+            transition.requested = Some(position);
+            transition.phase = Phase::FadingIn;
+        }
+        Phase::FadingIn => {
+            if game_state.map_loaded {
+                transition.alpha = (transition.alpha - step).max(0.);
+                if transition.alpha <= 0. {
+                    transition.phase = Phase::Idle;
+                }
+            }
+        }
+    }
+
+    for handle in overlay_query.iter() {
+        if let Some(material) = materials.get_mut(handle) {
+            material.color.set_a(transition.alpha);
+        }
+    }
+}